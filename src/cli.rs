@@ -0,0 +1,457 @@
+//! Searching from the command line, without starting the web server.
+
+use std::{
+    env,
+    io::{self, IsTerminal},
+    path::PathBuf,
+    process::ExitCode,
+    str::FromStr,
+    sync::Arc,
+};
+
+use crate::{
+    config::Config,
+    engines::{self, Engine, SearchQuery, SearchTab},
+    response::{
+        self, AutocompleteResponse, EngineState, RichContent, SearchResponse, SearchResult,
+    },
+};
+
+pub const USAGE: &str = "Usage: metasearch [config_path]
+       metasearch search <query> [--tab all|images] [--engines google,bing] [--format text|markdown|json] [--config path]
+       metasearch autocomplete <query> [--format text|json] [--config path]";
+
+#[derive(Debug, PartialEq)]
+pub struct CliArgs {
+    pub command: CliCommand,
+    pub query: String,
+    pub tab: SearchTab,
+    /// Only these engines are used, if they're enabled. They're looked up
+    /// after the config is read, since the config can define engines.
+    pub engines: Option<Vec<String>>,
+    pub format: OutputFormat,
+    pub config_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CliCommand {
+    Search,
+    Autocomplete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Markdown,
+    Json,
+}
+
+/// Parse the arguments (without the name of the binary). This returns `None`
+/// if they aren't for a command line search, which means we should start the
+/// web server.
+pub fn parse_args(args: &[String]) -> Result<Option<CliArgs>, String> {
+    let command = match args.first().map(String::as_str) {
+        Some("search") => CliCommand::Search,
+        Some("autocomplete") => CliCommand::Autocomplete,
+        _ => return Ok(None),
+    };
+
+    let mut query = Vec::new();
+    let mut tab = SearchTab::default();
+    let mut engines = None;
+    let mut format = OutputFormat::Text;
+    let mut config_path = None;
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {name}"))
+        };
+        match arg.as_str() {
+            "--tab" => {
+                let value = value("--tab")?;
+                tab = SearchTab::from_str(value).map_err(|()| format!("Unknown tab '{value}'"))?;
+            }
+            "--engines" => {
+                engines = Some(
+                    value("--engines")?
+                        .split(',')
+                        .map(|engine| engine.trim().to_string())
+                        .collect(),
+                );
+            }
+            "--format" => {
+                format = match value("--format")?.as_str() {
+                    "text" => OutputFormat::Text,
+                    "markdown" => OutputFormat::Markdown,
+                    "json" => OutputFormat::Json,
+                    other => return Err(format!("Unknown format '{other}'")),
+                };
+            }
+            "--config" => config_path = Some(PathBuf::from(value("--config")?)),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option '{arg}'")),
+            _ => query.push(arg.as_str()),
+        }
+    }
+
+    let query = query.join(" ");
+    if query.trim().is_empty() {
+        return Err("Missing query".to_string());
+    }
+    if command == CliCommand::Autocomplete && format == OutputFormat::Markdown {
+        return Err("Autocomplete doesn't support markdown".to_string());
+    }
+
+    Ok(Some(CliArgs {
+        command,
+        query,
+        tab,
+        engines,
+        format,
+        config_path,
+    }))
+}
+
+pub async fn run(args: CliArgs, mut config: Config) -> ExitCode {
+    if let Some(allowed_engines) = &args.engines {
+        let allowed_engines = match allowed_engines
+            .iter()
+            .map(|engine| Engine::from_str(engine).map_err(|()| engine))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(allowed_engines) => allowed_engines,
+            Err(engine) => {
+                eprintln!("Unknown engine '{engine}'");
+                return ExitCode::FAILURE;
+            }
+        };
+        let mut engines = config.engines.as_ref().clone();
+        engines.restrict_to(&allowed_engines);
+        config.engines = engines.into();
+    }
+
+    match args.command {
+        CliCommand::Search => search(args, config).await,
+        CliCommand::Autocomplete => autocomplete(args, config).await,
+    }
+}
+
+async fn search(args: CliArgs, config: Config) -> ExitCode {
+    let query = SearchQuery::new(args.query, Arc::new(config)).with_tab(args.tab);
+    // there's no pagination on the command line
+    let mut response = match response::search(query).await {
+        Ok(response) => response,
+        Err(err) => {
+            eprintln!("Search failed: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let colors = Colors::new();
+    for status in &response.engines {
+        if let (EngineState::Error, Some(error)) = (&status.status, &status.error) {
+            eprintln!(
+                "{}{} failed: {}{}",
+                colors.dim,
+                status.engine,
+                without_control_chars(error),
+                colors.reset
+            );
+        }
+    }
+    if args.format != OutputFormat::Json {
+        strip_control_chars(&mut response);
+    }
+
+    match args.format {
+        OutputFormat::Text => print!("{}", render_text(&response, &colors)),
+        OutputFormat::Markdown => print!("{}", render_markdown(&response)),
+        OutputFormat::Json => match serde_json::to_string_pretty(&response) {
+            Ok(json) => println!("{json}"),
+            Err(err) => {
+                eprintln!("Couldn't serialize results: {err}");
+                return ExitCode::FAILURE;
+            }
+        },
+    }
+    ExitCode::SUCCESS
+}
+
+async fn autocomplete(args: CliArgs, config: Config) -> ExitCode {
+    let suggestions = match engines::autocomplete(&config, &args.query).await {
+        Ok(suggestions) => suggestions,
+        Err(err) => {
+            eprintln!("Autocomplete failed: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.format == OutputFormat::Json {
+        let response = AutocompleteResponse {
+            query: args.query,
+            suggestions,
+        };
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&response).unwrap_or_default()
+        );
+    } else {
+        for suggestion in suggestions {
+            println!("{}", without_control_chars(&suggestion));
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// ANSI escape codes, which are empty if stdout isn't a terminal or `NO_COLOR`
+/// is set.
+struct Colors {
+    bold: &'static str,
+    dim: &'static str,
+    blue: &'static str,
+    green: &'static str,
+    reset: &'static str,
+}
+
+impl Colors {
+    fn new() -> Self {
+        if io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none() {
+            Colors {
+                bold: "\x1b[1m",
+                dim: "\x1b[2m",
+                blue: "\x1b[34m",
+                green: "\x1b[32m",
+                reset: "\x1b[0m",
+            }
+        } else {
+            Colors {
+                bold: "",
+                dim: "",
+                blue: "",
+                green: "",
+                reset: "",
+            }
+        }
+    }
+}
+
+/// Remove control characters (except for newlines) from text that came from an
+/// engine, so a malicious page can't send escape codes to the terminal.
+fn without_control_chars(s: &str) -> String {
+    s.chars()
+        .filter(|&c| c == '\n' || !c.is_control())
+        .collect()
+}
+
+fn strip_control_chars(response: &mut SearchResponse) {
+    let clean = |s: &mut String| *s = without_control_chars(s);
+    for result in &mut response.results {
+        match result {
+            SearchResult::Web {
+                url,
+                title,
+                description,
+                ..
+            } => [url, title, description].into_iter().for_each(clean),
+            SearchResult::Image {
+                image_url,
+                page_url,
+                title,
+                ..
+            } => [image_url, page_url, title].into_iter().for_each(clean),
+        }
+    }
+    if let Some(snippet) = &mut response.featured_snippet {
+        [
+            &mut snippet.url,
+            &mut snippet.title,
+            &mut snippet.description,
+        ]
+        .into_iter()
+        .for_each(clean);
+    }
+    for content in response.answer.iter_mut().chain(&mut response.infoboxes) {
+        clean(&mut content.text);
+    }
+}
+
+fn render_text(response: &SearchResponse, c: &Colors) -> String {
+    let mut out = String::new();
+
+    if let Some(answer) = &response.answer {
+        out.push_str(&format!("{}{}{}\n\n", c.bold, answer.text, c.reset));
+    }
+    if let Some(snippet) = &response.featured_snippet {
+        out.push_str(&format!(
+            "{}{}{}\n{}{}{}\n{}\n\n",
+            c.bold, snippet.title, c.reset, c.green, snippet.url, c.reset, snippet.description
+        ));
+    }
+
+    for (i, result) in response.results.iter().enumerate() {
+        match result {
+            SearchResult::Web {
+                url,
+                title,
+                description,
+                engines,
+                ..
+            } => {
+                out.push_str(&format!(
+                    "{}. {}{}{}{}\n   {}{url}{}\n",
+                    i + 1,
+                    c.bold,
+                    c.blue,
+                    title,
+                    c.reset,
+                    c.green,
+                    c.reset
+                ));
+                if !description.is_empty() {
+                    out.push_str(&format!("   {description}\n"));
+                }
+                out.push_str(&format!(
+                    "   {}{}{}\n\n",
+                    c.dim,
+                    engines.join(", "),
+                    c.reset
+                ));
+            }
+            SearchResult::Image {
+                image_url,
+                page_url,
+                title,
+                width,
+                height,
+                engines,
+                ..
+            } => {
+                out.push_str(&format!(
+                    "{}. {}{}{}{} ({width}x{height})\n   {}{image_url}{}\n   {page_url}\n   {}{}{}\n\n",
+                    i + 1,
+                    c.bold,
+                    c.blue,
+                    title,
+                    c.reset,
+                    c.green,
+                    c.reset,
+                    c.dim,
+                    engines.join(", "),
+                    c.reset
+                ));
+            }
+        }
+    }
+
+    for infobox in &response.infoboxes {
+        out.push_str(&format!(
+            "{}[{}]{}\n{}\n\n",
+            c.dim, infobox.engine, c.reset, infobox.text
+        ));
+    }
+
+    out
+}
+
+fn render_markdown(response: &SearchResponse) -> String {
+    let mut out = format!("# {}\n\n", response.query);
+
+    if let Some(answer) = &response.answer {
+        out.push_str(&format!("> {}\n\n", answer.text));
+    }
+    if let Some(snippet) = &response.featured_snippet {
+        out.push_str(&format!(
+            "**[{}]({})**\n\n{}\n\n",
+            escape_markdown(&snippet.title),
+            snippet.url,
+            snippet.description
+        ));
+    }
+
+    for (i, result) in response.results.iter().enumerate() {
+        match result {
+            SearchResult::Web {
+                url,
+                title,
+                description,
+                engines,
+                ..
+            } => {
+                out.push_str(&format!(
+                    "{}. [{}]({url})  \n   {description} _({})_\n",
+                    i + 1,
+                    escape_markdown(title),
+                    engines.join(", ")
+                ));
+            }
+            SearchResult::Image {
+                image_url,
+                page_url,
+                title,
+                engines,
+                ..
+            } => {
+                out.push_str(&format!(
+                    "{}. [![{}]({image_url})]({page_url}) _({})_\n",
+                    i + 1,
+                    escape_markdown(title),
+                    engines.join(", ")
+                ));
+            }
+        }
+    }
+
+    for RichContent { engine, text, .. } in &response.infoboxes {
+        out.push_str(&format!("\n## {engine}\n\n{text}\n"));
+    }
+
+    out
+}
+
+fn escape_markdown(s: &str) -> String {
+    s.replace('[', r"\[").replace(']', r"\]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(&args(&["config.toml"])), Ok(None));
+        assert_eq!(
+            parse_args(&args(&[
+                "search",
+                "rust",
+                "--tab",
+                "images",
+                "lang",
+                "--engines",
+                "google,bing"
+            ])),
+            Ok(Some(CliArgs {
+                command: CliCommand::Search,
+                query: "rust lang".to_string(),
+                tab: SearchTab::Images,
+                engines: Some(vec!["google".to_string(), "bing".to_string()]),
+                format: OutputFormat::Text,
+                config_path: None,
+            }))
+        );
+        assert!(parse_args(&args(&["search"])).is_err());
+        assert!(parse_args(&args(&["search", "a", "--format", "xml"])).is_err());
+    }
+
+    #[test]
+    fn test_without_control_chars() {
+        assert_eq!(
+            without_control_chars("evil\x1b]0;title\x07 text\r\nnext line"),
+            "evil]0;title text\nnext line"
+        );
+    }
+}
@@ -1,21 +1,84 @@
+//! The config file, and the defaults that are used for anything it leaves out.
+
 use std::{
     collections::HashMap,
-    fs,
+    fmt, fs,
     net::SocketAddr,
-    path::Path,
+    path::{Path, PathBuf},
+    str::FromStr,
     sync::{Arc, LazyLock},
 };
 
-use serde::Deserialize;
+use base64::Engine as _;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use ipnet::IpNet;
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize};
 use tracing::info;
 
 use crate::engines::Engine;
 
+/// Generated once so every default config signs image proxy URLs the same way.
+static DEFAULT_IMAGE_PROXY_SECRET: LazyLock<String> =
+    LazyLock::new(|| base64::engine::general_purpose::STANDARD.encode(rand::random::<[u8; 32]>()));
+
 impl Default for Config {
     fn default() -> Self {
         Config {
-            bind: "0.0.0.0:28019".parse().unwrap(),
-            api: false,
+            bind: vec![BindAddress::Tcp("0.0.0.0:28019".parse().unwrap())],
+            unix_socket_permissions: None,
+            trust_unix_socket: false,
+            public_url: None,
+            shutdown_grace_period_secs: 30,
+            tls: TlsConfig {
+                enabled: false,
+                cert: PathBuf::from("cert.pem"),
+                key: PathBuf::from("key.pem"),
+                reload_interval_secs: 60,
+                redirect_bind: None,
+            },
+            runtime: RuntimeConfig {
+                multi_threaded: false,
+                worker_threads: None,
+            },
+            api: ApiConfig {
+                enabled: false,
+                tokens: HashMap::new(),
+                public_autocomplete: true,
+                searxng_compatible: false,
+            },
+            trusted_proxies: vec![],
+            access: AccessConfig {
+                users: HashMap::new(),
+                allowed_networks: vec![],
+            },
+            metrics: MetricsConfig {
+                enabled: false,
+                bind: None,
+            },
+            stats: StatsConfig { admins: vec![] },
+            circuit_breaker: CircuitBreakerConfig {
+                enabled: false,
+                failure_threshold: 5,
+                cooldown_secs: 300,
+            },
+            logging: LoggingConfig {
+                json: false,
+                redact_queries: false,
+                otlp_endpoint: None,
+            },
+            readiness: ReadinessConfig { min_engines: 1 },
+            canary: CanaryConfig {
+                enabled: false,
+                queries: vec!["wikipedia".to_string()],
+                engines: vec![
+                    Engine::Google,
+                    Engine::Bing,
+                    Engine::Brave,
+                    Engine::Marginalia,
+                ],
+                interval_secs: 60 * 60,
+            },
             ui: UiConfig {
                 show_engine_list_separator: false,
                 show_version_info: false,
@@ -32,6 +95,15 @@ impl Default for Config {
                 proxy: ImageProxyConfig {
                     enabled: true,
                     max_download_size: 10_000_000,
+                    secret: DEFAULT_IMAGE_PROXY_SECRET.clone(),
+                    max_width: None,
+                    format: ThumbnailFormat::Jpeg,
+                    quality: 80,
+                    cache: ImageCacheConfig {
+                        enabled: false,
+                        path: PathBuf::from("image-cache"),
+                        max_size: 500_000_000,
+                    },
                 },
             },
             engines: Arc::new(EnginesConfig::default()),
@@ -48,73 +120,14 @@ impl Default for Config {
 
 impl Default for EnginesConfig {
     fn default() -> Self {
-        use toml::value::Value;
-
-        let mut map = HashMap::new();
-        // engines are enabled by default, so engines that aren't listed here are
-        // enabled
-
-        // main search engines
-        map.insert(Engine::Google, EngineConfig::new().with_weight(1.05));
-        map.insert(Engine::Bing, EngineConfig::new().with_weight(1.0));
-        map.insert(Engine::Brave, EngineConfig::new().with_weight(1.25));
-        map.insert(
-            Engine::Marginalia,
-            EngineConfig::new().with_weight(0.15).with_extra(
-                vec![(
-                    "args".to_string(),
-                    Value::Table(
-                        vec![
-                            ("profile".to_string(), Value::String("corpo".to_string())),
-                            ("js".to_string(), Value::String("default".to_string())),
-                            ("adtech".to_string(), Value::String("default".to_string())),
-                        ]
-                        .into_iter()
-                        .collect(),
-                    ),
-                )]
+        // the defaults (like weights and whether the engine is enabled) come from
+        // the engines themselves
+        Self {
+            map: Engine::all()
                 .into_iter()
+                .map(|engine| (engine, engine.default_config()))
                 .collect(),
-            ),
-        );
-
-        // additional search engines
-        map.insert(
-            Engine::GoogleScholar,
-            EngineConfig::new().with_weight(0.50).disabled(),
-        );
-        map.insert(
-            Engine::RightDao,
-            EngineConfig::new().with_weight(0.10).disabled(),
-        );
-        map.insert(
-            Engine::Stract,
-            EngineConfig::new().with_weight(0.15).disabled(),
-        );
-        map.insert(
-            Engine::Yep,
-            EngineConfig::new().with_weight(0.10).disabled(),
-        );
-
-        // calculators (give them a high weight so they're always the first thing in
-        // autocomplete)
-        map.insert(Engine::Numbat, EngineConfig::new().with_weight(10.0));
-        map.insert(
-            Engine::Fend,
-            EngineConfig::new().with_weight(10.0).disabled(),
-        );
-
-        // other engines
-        map.insert(
-            Engine::Mdn,
-            EngineConfig::new().with_extra(
-                vec![("max_sections".to_string(), Value::Integer(1))]
-                    .into_iter()
-                    .collect(),
-            ),
-        );
-
-        Self { map }
+        }
     }
 }
 
@@ -129,18 +142,22 @@ impl Default for EngineConfig {
 }
 static DEFAULT_ENGINE_CONFIG_REF: LazyLock<EngineConfig> = LazyLock::new(EngineConfig::default);
 impl EngineConfig {
+    /// An enabled engine with a weight of 1 and no options.
     pub fn new() -> Self {
         Self::default()
     }
+    /// The same config, with a different weight.
     pub fn with_weight(self, weight: f64) -> Self {
         Self { weight, ..self }
     }
+    /// The same config, but disabled.
     pub fn disabled(self) -> Self {
         Self {
             enabled: false,
             ..self
         }
     }
+    /// The same config, with different options.
     pub fn with_extra(self, extra: toml::Table) -> Self {
         Self { extra, ..self }
     }
@@ -148,22 +165,77 @@ impl EngineConfig {
 
 //
 
+/// The config for everything, with the defaults filled in for anything that
+/// wasn't set.
 #[derive(Debug, Clone)]
 pub struct Config {
-    pub bind: SocketAddr,
-    /// Whether the JSON API should be accessible.
-    pub api: bool,
+    /// The TCP addresses and Unix sockets that the web server listens on.
+    pub bind: Vec<BindAddress>,
+    /// The permissions that Unix sockets are created with, like `0o660`.
+    pub unix_socket_permissions: Option<u32>,
+    /// Whether the `Forwarded` or `X-Forwarded-For` headers are trusted on
+    /// requests that come through a Unix socket. Only turn this on if nothing
+    /// but your reverse proxy can connect to the socket.
+    pub trust_unix_socket: bool,
+    /// The URL that the instance can be reached at, like
+    /// `https://search.example.com`. Links in feeds and the OpenSearch
+    /// description use this, or the `Host` header if it isn't set.
+    pub public_url: Option<String>,
+    /// How long requests that are still running (like searches that are still
+    /// streaming) get to finish after we're told to shut down.
+    pub shutdown_grace_period_secs: u64,
+    /// The `[tls]` section.
+    pub tls: TlsConfig,
+    /// The `[runtime]` section.
+    pub runtime: RuntimeConfig,
+    /// The `[api]` section.
+    pub api: ApiConfig,
+    /// Reverse proxies that we trust to tell us the real IP of the client
+    /// through the `Forwarded` or `X-Forwarded-For` headers.
+    pub trusted_proxies: Vec<IpNet>,
+    /// The `[access]` section.
+    pub access: AccessConfig,
+    /// The `[metrics]` section.
+    pub metrics: MetricsConfig,
+    /// The `[stats]` section.
+    pub stats: StatsConfig,
+    /// The `[circuit_breaker]` section.
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// The `[readiness]` section.
+    pub readiness: ReadinessConfig,
+    /// The `[canary]` section.
+    pub canary: CanaryConfig,
+    /// The `[logging]` section.
+    pub logging: LoggingConfig,
+    /// The `[ui]` section.
     pub ui: UiConfig,
+    /// The `[image_search]` section.
     pub image_search: ImageSearchConfig,
+    /// The `[engines]` section.
     // wrapped in an arc to make Config cheaper to clone
     pub engines: Arc<EnginesConfig>,
+    /// The `[urls]` section.
     pub urls: UrlsConfig,
 }
 
 #[derive(Deserialize, Debug)]
-pub struct PartialConfig {
-    pub bind: Option<SocketAddr>,
-    pub api: Option<bool>,
+pub(crate) struct PartialConfig {
+    pub bind: Option<PartialBindConfig>,
+    pub unix_socket_permissions: Option<u32>,
+    pub trust_unix_socket: Option<bool>,
+    pub public_url: Option<String>,
+    pub shutdown_grace_period_secs: Option<u64>,
+    pub tls: Option<PartialTlsConfig>,
+    pub runtime: Option<PartialRuntimeConfig>,
+    pub api: Option<PartialDefaultableApiConfig>,
+    pub trusted_proxies: Option<Vec<IpNet>>,
+    pub access: Option<PartialAccessConfig>,
+    pub metrics: Option<PartialDefaultableMetricsConfig>,
+    pub stats: Option<PartialStatsConfig>,
+    pub circuit_breaker: Option<PartialCircuitBreakerConfig>,
+    pub readiness: Option<PartialReadinessConfig>,
+    pub canary: Option<PartialCanaryConfig>,
+    pub logging: Option<PartialLoggingConfig>,
     pub ui: Option<PartialUiConfig>,
     pub image_search: Option<PartialImageSearchConfig>,
     pub engines: Option<PartialEnginesConfig>,
@@ -171,9 +243,52 @@ pub struct PartialConfig {
 }
 
 impl Config {
-    pub fn overlay(&mut self, partial: PartialConfig) {
-        self.bind = partial.bind.unwrap_or(self.bind);
-        self.api = partial.api.unwrap_or(self.api);
+    pub(crate) fn overlay(&mut self, partial: PartialConfig) {
+        if let Some(bind) = partial.bind {
+            self.bind = match bind {
+                PartialBindConfig::One(address) => vec![address],
+                PartialBindConfig::Many(addresses) => addresses,
+            };
+        }
+        self.unix_socket_permissions = partial
+            .unix_socket_permissions
+            .or(self.unix_socket_permissions);
+        self.trust_unix_socket = partial.trust_unix_socket.unwrap_or(self.trust_unix_socket);
+        self.public_url = partial.public_url.or(self.public_url.clone());
+        self.shutdown_grace_period_secs = partial
+            .shutdown_grace_period_secs
+            .unwrap_or(self.shutdown_grace_period_secs);
+        self.tls.overlay(partial.tls.unwrap_or_default());
+        self.runtime.overlay(partial.runtime.unwrap_or_default());
+        if let Some(partial_api) = partial.api {
+            self.api.overlay(match partial_api {
+                PartialDefaultableApiConfig::Boolean(enabled) => PartialApiConfig {
+                    enabled: Some(enabled),
+                    ..Default::default()
+                },
+                PartialDefaultableApiConfig::Full(full) => full,
+            });
+        }
+        self.trusted_proxies = partial
+            .trusted_proxies
+            .unwrap_or(self.trusted_proxies.clone());
+        self.access.overlay(partial.access.unwrap_or_default());
+        if let Some(partial_metrics) = partial.metrics {
+            self.metrics.overlay(match partial_metrics {
+                PartialDefaultableMetricsConfig::Boolean(enabled) => PartialMetricsConfig {
+                    enabled: Some(enabled),
+                    ..Default::default()
+                },
+                PartialDefaultableMetricsConfig::Full(full) => full,
+            });
+        }
+        self.stats.overlay(partial.stats.unwrap_or_default());
+        self.circuit_breaker
+            .overlay(partial.circuit_breaker.unwrap_or_default());
+        self.readiness
+            .overlay(partial.readiness.unwrap_or_default());
+        self.canary.overlay(partial.canary.unwrap_or_default());
+        self.logging.overlay(partial.logging.unwrap_or_default());
         self.ui.overlay(partial.ui.unwrap_or_default());
         self.image_search
             .overlay(partial.image_search.unwrap_or_default());
@@ -186,22 +301,454 @@ impl Config {
     }
 }
 
+/// An address that the web server listens on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindAddress {
+    /// Written as `ip:port` in the config.
+    Tcp(SocketAddr),
+    /// Written as `unix:/path/to/socket` in the config.
+    Unix(PathBuf),
+}
+
+impl FromStr for BindAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            return Ok(BindAddress::Unix(PathBuf::from(path)));
+        }
+        s.parse()
+            .map(BindAddress::Tcp)
+            .map_err(|_| format!("invalid bind address '{s}'"))
+    }
+}
+
+impl<'de> Deserialize<'de> for BindAddress {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for BindAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindAddress::Tcp(addr) => write!(f, "http://{addr}"),
+            BindAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// `bind` can be a single address or a list of them.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum PartialBindConfig {
+    One(BindAddress),
+    Many(Vec<BindAddress>),
+}
+
+/// How the Tokio runtime is set up.
+#[derive(Debug, Clone)]
+pub struct RuntimeConfig {
+    /// Whether requests should be handled on multiple threads. Parsing and
+    /// other slow work always happens on separate threads, so this is mostly
+    /// useful for busy instances.
+    pub multi_threaded: bool,
+    /// The number of threads used by the multi-threaded runtime. Defaults to
+    /// the number of CPU cores.
+    pub worker_threads: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct PartialRuntimeConfig {
+    pub multi_threaded: Option<bool>,
+    pub worker_threads: Option<usize>,
+}
+
+impl RuntimeConfig {
+    pub(crate) fn overlay(&mut self, partial: PartialRuntimeConfig) {
+        self.multi_threaded = partial.multi_threaded.unwrap_or(self.multi_threaded);
+        self.worker_threads = partial.worker_threads.or(self.worker_threads);
+    }
+}
+
+/// The JSON API, for other programs that want to search.
+#[derive(Debug, Clone)]
+pub struct ApiConfig {
+    /// Whether the JSON API should be accessible.
+    pub enabled: bool,
+    /// The tokens that can be used to access the API, keyed by a name that's
+    /// used in logs. If this is empty then the API doesn't require a token.
+    pub tokens: HashMap<String, ApiTokenConfig>,
+    /// Whether `/autocomplete` can be used without a token. The search bar and
+    /// browsers can't send one, so they need this to autocomplete.
+    pub public_autocomplete: bool,
+    /// Serve the same JSON as SearXNG's `/search?format=json` at
+    /// `/searxng/search`, so tools that were made for it work with metasearch
+    /// too.
+    pub searxng_compatible: bool,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub(crate) enum PartialDefaultableApiConfig {
+    Boolean(bool),
+    Full(PartialApiConfig),
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub(crate) struct PartialApiConfig {
+    pub enabled: Option<bool>,
+    pub tokens: Option<HashMap<String, ApiTokenConfig>>,
+    pub public_autocomplete: Option<bool>,
+    pub searxng_compatible: Option<bool>,
+}
+
+impl ApiConfig {
+    pub(crate) fn overlay(&mut self, partial: PartialApiConfig) {
+        self.enabled = partial.enabled.unwrap_or(self.enabled);
+        self.tokens.extend(partial.tokens.unwrap_or_default());
+        self.public_autocomplete = partial
+            .public_autocomplete
+            .unwrap_or(self.public_autocomplete);
+        self.searxng_compatible = partial
+            .searxng_compatible
+            .unwrap_or(self.searxng_compatible);
+    }
+}
+
+/// A token that can be used to access the API.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiTokenConfig {
+    /// The value that has to be passed in the `Authorization: Bearer` header.
+    pub token: String,
+    /// The engines that this token is allowed to use. All of the enabled
+    /// engines are allowed if this isn't set.
+    pub engines: Option<Vec<Engine>>,
+    /// The maximum number of requests that can be made with this token per
+    /// minute.
+    pub rate_limit: Option<u32>,
+    /// When the token stops being accepted.
+    pub expires: Option<TokenExpiry>,
+}
+
+/// A date or datetime from the config, which is assumed to be in UTC if it
+/// doesn't have an offset.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "toml::value::Datetime")]
+pub struct TokenExpiry(pub DateTime<Utc>);
+
+impl TryFrom<toml::value::Datetime> for TokenExpiry {
+    type Error = String;
+
+    fn try_from(datetime: toml::value::Datetime) -> Result<Self, Self::Error> {
+        if datetime.date.is_none() {
+            return Err(format!("token expiry {datetime} is missing a date"));
+        }
+        let expires = datetime.to_string();
+        let expires = if let Ok(datetime) = DateTime::parse_from_rfc3339(&expires) {
+            datetime.with_timezone(&Utc)
+        } else if let Ok(datetime) = NaiveDateTime::parse_from_str(&expires, "%Y-%m-%dT%H:%M:%S%.f")
+        {
+            datetime.and_utc()
+        } else if let Ok(date) = NaiveDate::parse_from_str(&expires, "%Y-%m-%d") {
+            date.and_hms_opt(0, 0, 0).unwrap().and_utc()
+        } else {
+            return Err(format!("couldn't parse token expiry {expires}"));
+        };
+        Ok(TokenExpiry(expires))
+    }
+}
+
+/// Restrictions on who can use the instance. Every restriction that's
+/// configured has to pass.
+#[derive(Debug, Clone)]
+pub struct AccessConfig {
+    /// Usernames and their argon2 password hashes (in the PHC string format).
+    /// If this isn't empty then HTTP basic auth is required.
+    pub users: HashMap<String, String>,
+    /// If this isn't empty then only clients in these networks can access the
+    /// instance.
+    pub allowed_networks: Vec<IpNet>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct PartialAccessConfig {
+    pub users: Option<HashMap<String, String>>,
+    pub allowed_networks: Option<Vec<IpNet>>,
+}
+
+impl AccessConfig {
+    pub(crate) fn overlay(&mut self, partial: PartialAccessConfig) {
+        self.users.extend(partial.users.unwrap_or_default());
+        self.allowed_networks = partial
+            .allowed_networks
+            .unwrap_or(self.allowed_networks.clone());
+    }
+}
+
+/// Prometheus metrics.
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    /// Whether Prometheus metrics should be served at `/metrics`.
+    pub enabled: bool,
+    /// If this is set then the metrics are served on this address instead of
+    /// the main one, so they can be kept private.
+    pub bind: Option<SocketAddr>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub(crate) enum PartialDefaultableMetricsConfig {
+    Boolean(bool),
+    Full(PartialMetricsConfig),
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub(crate) struct PartialMetricsConfig {
+    pub enabled: Option<bool>,
+    pub bind: Option<SocketAddr>,
+}
+
+impl MetricsConfig {
+    pub(crate) fn overlay(&mut self, partial: PartialMetricsConfig) {
+        self.enabled = partial.enabled.unwrap_or(self.enabled);
+        self.bind = partial.bind.or(self.bind);
+    }
+}
+
+/// Serving HTTPS ourselves, so small instances don't need a reverse proxy.
+/// Unix sockets are always served over plain HTTP.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// Whether the TCP addresses are served over HTTPS.
+    pub enabled: bool,
+    /// The PEM file with the certificate chain.
+    pub cert: PathBuf,
+    /// The PEM file with the private key.
+    pub key: PathBuf,
+    /// How often to check whether the certificate or key changed, so renewed
+    /// certificates are used without restarting.
+    pub reload_interval_secs: u64,
+    /// If this is set then plain HTTP requests to this address are redirected
+    /// to HTTPS.
+    pub redirect_bind: Option<SocketAddr>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct PartialTlsConfig {
+    pub enabled: Option<bool>,
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    pub reload_interval_secs: Option<u64>,
+    pub redirect_bind: Option<SocketAddr>,
+}
+
+impl TlsConfig {
+    pub(crate) fn overlay(&mut self, partial: PartialTlsConfig) {
+        self.enabled = partial.enabled.unwrap_or(self.enabled);
+        self.cert = partial.cert.unwrap_or(self.cert.clone());
+        self.key = partial.key.unwrap_or(self.key.clone());
+        self.reload_interval_secs = partial
+            .reload_interval_secs
+            .unwrap_or(self.reload_interval_secs);
+        self.redirect_bind = partial.redirect_bind.or(self.redirect_bind);
+    }
+}
+
+/// The engine health page, at `/stats`.
+#[derive(Debug, Clone)]
+pub struct StatsConfig {
+    /// The users (from `access.users`) that can see the engine health page at
+    /// `/stats`. The page is disabled if this is empty.
+    pub admins: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct PartialStatsConfig {
+    pub admins: Option<Vec<String>>,
+}
+
+impl StatsConfig {
+    pub(crate) fn overlay(&mut self, partial: PartialStatsConfig) {
+        self.admins = partial.admins.unwrap_or(self.admins.clone());
+    }
+}
+
+/// Stop sending requests to engines that keep failing for a while, so broken
+/// engines don't slow down every search.
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    /// Whether failing engines are skipped.
+    pub enabled: bool,
+    /// How many requests to an engine have to fail in a row before it's
+    /// skipped.
+    pub failure_threshold: u32,
+    /// How long an engine is skipped for before we try it again.
+    pub cooldown_secs: u64,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct PartialCircuitBreakerConfig {
+    pub enabled: Option<bool>,
+    pub failure_threshold: Option<u32>,
+    pub cooldown_secs: Option<u64>,
+}
+
+impl CircuitBreakerConfig {
+    pub(crate) fn overlay(&mut self, partial: PartialCircuitBreakerConfig) {
+        self.enabled = partial.enabled.unwrap_or(self.enabled);
+        self.failure_threshold = partial.failure_threshold.unwrap_or(self.failure_threshold);
+        self.cooldown_secs = partial.cooldown_secs.unwrap_or(self.cooldown_secs);
+    }
+}
+
+/// The readiness check, at `/readyz`.
+#[derive(Debug, Clone)]
+pub struct ReadinessConfig {
+    /// `/readyz` fails if fewer than this many search engines are enabled and
+    /// not failing (their circuit breaker is open or they're degraded).
+    pub min_engines: usize,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct PartialReadinessConfig {
+    pub min_engines: Option<usize>,
+}
+
+impl ReadinessConfig {
+    pub(crate) fn overlay(&mut self, partial: PartialReadinessConfig) {
+        self.min_engines = partial.min_engines.unwrap_or(self.min_engines);
+    }
+}
+
+/// Queries that are regularly searched for in the background, so we notice
+/// when an engine stops returning results (usually because its markup
+/// changed).
+#[derive(Debug, Clone)]
+pub struct CanaryConfig {
+    /// Whether the canary searches are run.
+    pub enabled: bool,
+    /// These should be queries that always have results.
+    pub queries: Vec<String>,
+    /// The engines that are checked, if they're enabled. Engines that don't
+    /// always have results (like answer engines) shouldn't be here.
+    pub engines: Vec<Engine>,
+    /// How long to wait between canary searches.
+    pub interval_secs: u64,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct PartialCanaryConfig {
+    pub enabled: Option<bool>,
+    pub queries: Option<Vec<String>>,
+    pub engines: Option<Vec<Engine>>,
+    pub interval_secs: Option<u64>,
+}
+
+impl CanaryConfig {
+    pub(crate) fn overlay(&mut self, partial: PartialCanaryConfig) {
+        self.enabled = partial.enabled.unwrap_or(self.enabled);
+        self.queries = partial.queries.unwrap_or(self.queries.clone());
+        self.engines = partial.engines.unwrap_or(self.engines.clone());
+        self.interval_secs = partial.interval_secs.unwrap_or(self.interval_secs);
+    }
+}
+
+/// What gets logged, and how.
+#[derive(Debug, Clone)]
+pub struct LoggingConfig {
+    /// Log one JSON object per line instead of human-readable text.
+    pub json: bool,
+    /// Leave search queries and client IPs out of logs.
+    pub redact_queries: bool,
+    /// Send traces to an OpenTelemetry collector at this URL (like
+    /// `http://localhost:4318/v1/traces`). This requires metasearch to be
+    /// built with the `otel` feature.
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct PartialLoggingConfig {
+    pub json: Option<bool>,
+    pub redact_queries: Option<bool>,
+    pub otlp_endpoint: Option<String>,
+}
+
+impl LoggingConfig {
+    pub(crate) fn overlay(&mut self, partial: PartialLoggingConfig) {
+        self.json = partial.json.unwrap_or(self.json);
+        self.redact_queries = partial.redact_queries.unwrap_or(self.redact_queries);
+        self.otlp_endpoint = partial.otlp_endpoint.or(self.otlp_endpoint.clone());
+    }
+
+    /// Returns the query, or a placeholder if queries should be redacted.
+    pub fn loggable_query<'a>(&self, query: &'a str) -> &'a str {
+        if self.redact_queries {
+            "[redacted]"
+        } else {
+            query
+        }
+    }
+
+    /// Returns the error message, with the query and the query strings of any
+    /// URLs taken out if queries should be redacted. Errors from requests
+    /// include the URL, which usually has the query in it.
+    pub fn loggable_error(&self, error: &impl fmt::Display, query: &str) -> String {
+        static URL_QUERY_REGEX: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r#"(https?://[^\s?#"'()<>]*)[?#][^\s"'()<>]*"#).unwrap());
+
+        let message = error.to_string();
+        if !self.redact_queries {
+            return message;
+        }
+        let mut message = URL_QUERY_REGEX
+            .replace_all(&message, "$1?[redacted]")
+            .into_owned();
+        let query = query.trim();
+        if !query.is_empty() {
+            let encoded_queries = [
+                urlencoding::encode(query).into_owned(),
+                url::form_urlencoded::byte_serialize(query.as_bytes()).collect(),
+            ];
+            message = message.replace(query, "[redacted]");
+            for encoded_query in encoded_queries {
+                message = message.replace(&encoded_query, "[redacted]");
+            }
+        }
+        message
+    }
+}
+
+/// The look of the web interface.
 #[derive(Debug, Clone)]
 pub struct UiConfig {
+    /// Whether the engines next to each result are separated by dots, with
+    /// spaces instead of underscores in their ids.
     pub show_engine_list_separator: bool,
+    /// Whether the version (and commit, if it's known) is shown on the index
+    /// page.
     pub show_version_info: bool,
     /// Settings are always accessible anyways, this just controls whether the
     /// link to them in the index page is visible.
     pub show_settings_link: bool,
+    /// The name that's shown in the title and the OpenSearch description.
     pub site_name: String,
+    /// Whether the search box suggests queries as you type.
     pub show_autocomplete: bool,
+    /// A stylesheet that's loaded after the default one.
     pub stylesheet_url: String,
+    /// Css that's added after the default stylesheet.
     pub stylesheet_str: String,
+    /// The url of the favicon, if it shouldn't be the default one.
     pub favicon_url: String,
 }
 
 #[derive(Deserialize, Debug, Default)]
-pub struct PartialUiConfig {
+pub(crate) struct PartialUiConfig {
     pub show_engine_list_separator: Option<bool>,
     pub show_version_info: Option<bool>,
     pub show_settings_link: Option<bool>,
@@ -214,7 +761,7 @@ pub struct PartialUiConfig {
 }
 
 impl UiConfig {
-    pub fn overlay(&mut self, partial: PartialUiConfig) {
+    pub(crate) fn overlay(&mut self, partial: PartialUiConfig) {
         self.show_engine_list_separator = partial
             .show_engine_list_separator
             .unwrap_or(self.show_engine_list_separator);
@@ -234,28 +781,33 @@ impl UiConfig {
     }
 }
 
+/// The "Images" tab.
 #[derive(Debug, Clone)]
 pub struct ImageSearchConfig {
+    /// Whether the tab is shown.
     pub enabled: bool,
+    /// Whether the engines that found each image are shown.
     pub show_engines: bool,
+    /// The `[image_search.proxy]` section.
     pub proxy: ImageProxyConfig,
 }
 
 #[derive(Deserialize, Debug, Default)]
-pub struct PartialImageSearchConfig {
+pub(crate) struct PartialImageSearchConfig {
     pub enabled: Option<bool>,
     pub show_engines: Option<bool>,
     pub proxy: Option<PartialImageProxyConfig>,
 }
 
 impl ImageSearchConfig {
-    pub fn overlay(&mut self, partial: PartialImageSearchConfig) {
+    pub(crate) fn overlay(&mut self, partial: PartialImageSearchConfig) {
         self.enabled = partial.enabled.unwrap_or(self.enabled);
         self.show_engines = partial.show_engines.unwrap_or(self.show_engines);
         self.proxy.overlay(partial.proxy.unwrap_or_default());
     }
 }
 
+/// Proxying images through our server.
 #[derive(Debug, Clone)]
 pub struct ImageProxyConfig {
     /// Whether we should proxy remote images through our server. This is mostly
@@ -263,41 +815,106 @@ pub struct ImageProxyConfig {
     pub enabled: bool,
     /// The maximum size of an image that can be proxied. This is in bytes.
     pub max_download_size: u64,
+    /// The key used to sign the URLs that we give to the image proxy, so it
+    /// only fetches images from our own result pages. If this isn't set then
+    /// a random one is generated on startup, which means that proxied URLs
+    /// stop working after restarting.
+    pub secret: String,
+    /// If this is set, images wider than this are shrunk to this width (in
+    /// pixels) and re-encoded before being sent. GIFs are never resized so
+    /// they stay animated.
+    pub max_width: Option<u32>,
+    /// The format that resized images are encoded as. WebP thumbnails are
+    /// lossless, so they're usually bigger than JPEG ones.
+    pub format: ThumbnailFormat,
+    /// The JPEG quality of resized images, from 1 to 100.
+    pub quality: u8,
+    /// The `[image_search.proxy.cache]` section.
+    pub cache: ImageCacheConfig,
+}
+
+/// The format that resized images are encoded as.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFormat {
+    /// Lossless WebP.
+    Webp,
+    /// JPEG, with the configured quality.
+    Jpeg,
+}
+
+/// An on-disk cache for the image proxy. Once it's bigger than `max_size`, the
+/// least recently used images are deleted.
+#[derive(Debug, Clone)]
+pub struct ImageCacheConfig {
+    /// Whether proxied images are cached.
+    pub enabled: bool,
+    /// The directory that the cache is stored in.
+    pub path: PathBuf,
+    /// The maximum size of the cache in bytes.
+    pub max_size: u64,
 }
 
 #[derive(Deserialize, Debug, Default)]
-pub struct PartialImageProxyConfig {
+pub(crate) struct PartialImageCacheConfig {
+    pub enabled: Option<bool>,
+    pub path: Option<PathBuf>,
+    pub max_size: Option<u64>,
+}
+
+impl ImageCacheConfig {
+    pub(crate) fn overlay(&mut self, partial: PartialImageCacheConfig) {
+        self.enabled = partial.enabled.unwrap_or(self.enabled);
+        self.path = partial.path.unwrap_or(self.path.clone());
+        self.max_size = partial.max_size.unwrap_or(self.max_size);
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct PartialImageProxyConfig {
     pub enabled: Option<bool>,
     pub max_download_size: Option<u64>,
+    pub secret: Option<String>,
+    pub max_width: Option<u32>,
+    pub format: Option<ThumbnailFormat>,
+    pub quality: Option<u8>,
+    pub cache: Option<PartialImageCacheConfig>,
 }
 
 impl ImageProxyConfig {
-    pub fn overlay(&mut self, partial: PartialImageProxyConfig) {
+    pub(crate) fn overlay(&mut self, partial: PartialImageProxyConfig) {
         self.enabled = partial.enabled.unwrap_or(self.enabled);
         self.max_download_size = partial.max_download_size.unwrap_or(self.max_download_size);
+        self.secret = partial.secret.unwrap_or(self.secret.clone());
+        self.max_width = partial.max_width.or(self.max_width);
+        self.format = partial.format.unwrap_or(self.format);
+        self.quality = partial.quality.unwrap_or(self.quality);
+        self.cache.overlay(partial.cache.unwrap_or_default());
     }
 }
 
+/// The config of every engine.
 #[derive(Debug, Clone)]
 pub struct EnginesConfig {
+    /// The engines that were configured.
     pub map: HashMap<Engine, EngineConfig>,
 }
 
 #[derive(Deserialize, Debug, Default)]
-pub struct PartialEnginesConfig {
+pub(crate) struct PartialEnginesConfig {
     #[serde(flatten)]
     pub map: HashMap<Engine, PartialDefaultableEngineConfig>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
 #[serde(untagged)]
-pub enum PartialDefaultableEngineConfig {
+pub(crate) enum PartialDefaultableEngineConfig {
     Boolean(bool),
     Full(PartialEngineConfig),
 }
 
 impl EnginesConfig {
-    pub fn overlay(&mut self, partial: PartialEnginesConfig) {
+    pub(crate) fn overlay(&mut self, partial: PartialEnginesConfig) {
         for (key, value) in partial.map {
             let full = match value {
                 PartialDefaultableEngineConfig::Boolean(enabled) => PartialEngineConfig {
@@ -309,20 +926,35 @@ impl EnginesConfig {
             if let Some(existing) = self.map.get_mut(&key) {
                 existing.overlay(full);
             } else {
-                let mut new = EngineConfig::default();
+                let mut new = key.default_config();
                 new.overlay(full);
                 self.map.insert(key, new);
             }
         }
     }
 
+    /// The config of an engine, or the default config if there isn't one.
     pub fn get(&self, engine: Engine) -> &EngineConfig {
         self.map.get(&engine).unwrap_or(&DEFAULT_ENGINE_CONFIG_REF)
     }
+
+    /// Disable every engine that isn't in the list.
+    pub fn restrict_to(&mut self, allowed_engines: &[Engine]) {
+        for engine in Engine::all() {
+            if !allowed_engines.contains(&engine) {
+                self.map
+                    .entry(engine)
+                    .or_insert_with(|| engine.default_config())
+                    .enabled = false;
+            }
+        }
+    }
 }
 
+/// The config of a single engine.
 #[derive(Debug, Clone)]
 pub struct EngineConfig {
+    /// Whether the engine is used in searches.
     pub enabled: bool,
     /// The priority of this engine relative to the other engines.
     pub weight: f64,
@@ -331,7 +963,7 @@ pub struct EngineConfig {
 }
 
 #[derive(Deserialize, Clone, Debug, Default)]
-pub struct PartialEngineConfig {
+pub(crate) struct PartialEngineConfig {
     pub enabled: Option<bool>,
     pub weight: Option<f64>,
     #[serde(flatten)]
@@ -339,17 +971,22 @@ pub struct PartialEngineConfig {
 }
 
 impl EngineConfig {
-    pub fn overlay(&mut self, partial: PartialEngineConfig) {
+    pub(crate) fn overlay(&mut self, partial: PartialEngineConfig) {
         self.enabled = partial.enabled.unwrap_or(self.enabled);
         self.weight = partial.weight.unwrap_or(self.weight);
         self.extra.extend(partial.extra);
     }
+
+    /// Parse the extra options into the engine's own type.
+    pub fn options<T: DeserializeOwned>(&self) -> eyre::Result<T> {
+        Ok(toml::Value::Table(self.extra.clone()).try_into()?)
+    }
 }
 
 impl Config {
+    /// Like [`Config::read`], but write the default config to `config_path`
+    /// first if there's nothing there.
     pub fn read_or_create(config_path: &Path) -> eyre::Result<Self> {
-        let mut config = Config::default();
-
         if !config_path.exists() {
             info!("No config found, creating one at {config_path:?}");
             let default_config_str = include_str!("../config-default.toml");
@@ -359,18 +996,37 @@ impl Config {
             fs::write(config_path, default_config_str)?;
         }
 
-        let given_config = toml::from_str::<PartialConfig>(&fs::read_to_string(config_path)?)?;
+        Self::read(config_path)
+    }
+
+    /// Read the config, or use the default one if the file doesn't exist.
+    ///
+    /// Engines from the `[custom_engines]` section aren't registered by this,
+    /// so call [`crate::engines::custom::register_from_file`] first if the
+    /// `[engines]` section refers to them.
+    pub fn read(config_path: &Path) -> eyre::Result<Self> {
+        if !config_path.exists() {
+            return Ok(Config::default());
+        }
+
+        let config_str = fs::read_to_string(config_path)?;
+        let given_config = toml::from_str::<PartialConfig>(&config_str)?;
+        let mut config = Config::default();
         config.overlay(given_config);
         Ok(config)
     }
 }
 
+/// A host and path, like `example.com/docs/`.
 #[derive(Debug, Clone, PartialEq)]
 pub struct HostAndPath {
+    /// The host. If it starts with a dot then it includes subdomains.
     pub host: String,
+    /// The path, without the leading slash.
     pub path: String,
 }
 impl HostAndPath {
+    /// Split something like `example.com/docs/` into its host and path.
     pub fn new(s: &str) -> Self {
         let (host, path) = s.split_once('/').unwrap_or((s, ""));
         Self {
@@ -380,20 +1036,24 @@ impl HostAndPath {
     }
 }
 
+/// Changes that are made to the urls of results.
 #[derive(Debug, Clone)]
 pub struct UrlsConfig {
+    /// Hosts and paths that are rewritten to other ones, like for alternative
+    /// frontends.
     pub replace: Vec<(HostAndPath, HostAndPath)>,
+    /// Multipliers for the scores of results from matching hosts and paths.
     pub weight: Vec<(HostAndPath, f64)>,
 }
 #[derive(Deserialize, Debug, Default)]
-pub struct PartialUrlsConfig {
+pub(crate) struct PartialUrlsConfig {
     #[serde(default)]
     pub replace: HashMap<String, String>,
     #[serde(default)]
     pub weight: HashMap<String, f64>,
 }
 impl UrlsConfig {
-    pub fn overlay(&mut self, partial: PartialUrlsConfig) {
+    pub(crate) fn overlay(&mut self, partial: PartialUrlsConfig) {
         for (from, to) in partial.replace {
             let from = HostAndPath::new(&from);
             if to.is_empty() {
@@ -420,3 +1080,26 @@ impl UrlsConfig {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacted_errors_dont_have_the_query() {
+        let mut logging = Config::default().logging;
+        let query = "my secret query";
+        let error = "error sending request for url (https://www.google.com/search?q=my+secret+query&nfpr=1): \
+                     timed out, path was https://example.com/my%20secret%20query";
+
+        assert_eq!(logging.loggable_error(&error, query), error);
+
+        logging.redact_queries = true;
+        let message = logging.loggable_error(&error, query);
+        assert!(!message.contains("secret"), "{message}");
+        assert!(
+            message.contains("https://www.google.com/search?[redacted]"),
+            "{message}"
+        );
+    }
+}
@@ -1,9 +1,25 @@
 use maud::html;
 
-use crate::engines::{EngineResponse, SearchQuery};
+use crate::engines::{
+    async_trait, Engine, EngineImpl, EngineResponse, IntoRequestResponseResult, RequestResponse,
+    SearchQuery,
+};
 
 use super::regex;
 
+pub struct ColorPicker;
+
+#[async_trait]
+impl EngineImpl for ColorPicker {
+    fn id(&self) -> &'static str {
+        Engine::ColorPicker.id()
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+}
+
 pub async fn request(query: &SearchQuery) -> EngineResponse {
     let matched_colors = MatchedColorModel::new(&query.query);
 
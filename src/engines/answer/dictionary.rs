@@ -5,10 +5,30 @@ use maud::{html, PreEscaped};
 use serde::Deserialize;
 use url::Url;
 
-use crate::engines::{EngineResponse, HttpResponse, RequestResponse, CLIENT};
+use crate::engines::{
+    async_trait, Engine, EngineImpl, EngineResponse, HttpResponse, IntoRequestResponseResult,
+    RequestResponse, SearchQuery, CLIENT,
+};
 
 use super::regex;
 
+pub struct Dictionary;
+
+#[async_trait]
+impl EngineImpl for Dictionary {
+    fn id(&self) -> &'static str {
+        Engine::Dictionary.id()
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+
+    fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        parse_response(res)
+    }
+}
+
 pub async fn request(query: &str) -> RequestResponse {
     // if the query starts with "define " then use that, otherwise abort
     let re = regex!(r"^define\s+(\w+)$");
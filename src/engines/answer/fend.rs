@@ -2,14 +2,51 @@ use fend_core::SpanKind;
 use maud::{html, PreEscaped};
 use std::sync::{atomic::AtomicU32, atomic::Ordering, LazyLock};
 
-use crate::engines::EngineResponse;
+use crate::engines::{
+    async_trait, run_blocking, AutocompleteKind, Engine, EngineImpl, EngineResponse,
+    IntoRequestResponseResult, RequestAutocompleteResponse, RequestResponse, SearchQuery,
+};
 
 use super::regex;
 
+pub struct Fend;
+
+#[async_trait]
+impl EngineImpl for Fend {
+    fn id(&self) -> &'static str {
+        Engine::Fend.id()
+    }
+
+    fn default_weight(&self) -> f64 {
+        10.0
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+
+    fn autocomplete_kind(&self) -> AutocompleteKind {
+        AutocompleteKind::Instant
+    }
+
+    fn request_autocomplete(&self, query: &str) -> Option<RequestAutocompleteResponse> {
+        Some(request_autocomplete(query).into())
+    }
+}
+
 pub async fn request(query: &str) -> EngineResponse {
     let query = clean_query(query);
 
-    let Some(result_html) = evaluate_to_html(&query, true) else {
+    let Ok(Some(result_html)) = run_blocking({
+        let query = query.clone();
+        move || evaluate_to_html(&query, true)
+    })
+    .await
+    else {
         return EngineResponse::new();
     };
 
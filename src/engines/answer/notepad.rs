@@ -1,9 +1,25 @@
 use maud::html;
 
-use crate::engines::{EngineResponse, SearchQuery};
+use crate::engines::{
+    async_trait, Engine, EngineImpl, EngineResponse, IntoRequestResponseResult, RequestResponse,
+    SearchQuery,
+};
 
 use super::regex;
 
+pub struct Notepad;
+
+#[async_trait]
+impl EngineImpl for Notepad {
+    fn id(&self) -> &'static str {
+        Engine::Notepad.id()
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+}
+
 pub async fn request(query: &SearchQuery) -> EngineResponse {
     if !regex!("^(note|text|code) ?(pad|book|edit(or|er)?)$").is_match(&query.query.to_lowercase())
     {
@@ -10,15 +10,43 @@ use numbat::{
 };
 use tracing::debug;
 
-use crate::engines::EngineResponse;
+use crate::engines::{
+    async_trait, run_blocking, AutocompleteKind, Engine, EngineImpl, EngineResponse,
+    IntoRequestResponseResult, RequestAutocompleteResponse, RequestResponse, SearchQuery,
+};
+
+pub struct Numbat;
+
+#[async_trait]
+impl EngineImpl for Numbat {
+    fn id(&self) -> &'static str {
+        Engine::Numbat.id()
+    }
+
+    fn default_weight(&self) -> f64 {
+        10.0
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+
+    fn autocomplete_kind(&self) -> AutocompleteKind {
+        AutocompleteKind::Instant
+    }
+
+    fn request_autocomplete(&self, query: &str) -> Option<RequestAutocompleteResponse> {
+        Some(request_autocomplete(query).into())
+    }
+}
 
 pub async fn request(query: &str) -> EngineResponse {
     let query = clean_query(query);
 
-    let Some(NumbatResponse {
+    let Ok(Some(NumbatResponse {
         query_html,
         result_html,
-    }) = evaluate(&query)
+    })) = run_blocking(move || evaluate(&query)).await
     else {
         return EngineResponse::new();
     };
@@ -5,10 +5,30 @@ use serde::Deserialize;
 use tracing::error;
 use url::Url;
 
-use crate::engines::{EngineResponse, RequestResponse, CLIENT};
+use crate::engines::{
+    async_trait, Engine, EngineImpl, EngineResponse, HttpResponse, IntoRequestResponseResult,
+    RequestResponse, SearchQuery, CLIENT,
+};
 
 use super::regex;
 
+pub struct Thesaurus;
+
+#[async_trait]
+impl EngineImpl for Thesaurus {
+    fn id(&self) -> &'static str {
+        Engine::Thesaurus.id()
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+
+    fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        parse_response(res.into())
+    }
+}
+
 pub async fn request(query: &str) -> RequestResponse {
     let re = regex!(r"^synonym(?:s?) for\s+(\w+)$");
     let re2 = regex!(r"^(\w+)\s+synonym(?:s?)$");
@@ -2,10 +2,26 @@ use chrono::{DateTime, TimeZone};
 use chrono_tz::{OffsetComponents, Tz};
 use maud::html;
 
-use crate::engines::EngineResponse;
+use crate::engines::{
+    async_trait, Engine, EngineImpl, EngineResponse, IntoRequestResponseResult, RequestResponse,
+    SearchQuery,
+};
 
 use super::regex;
 
+pub struct Timezone;
+
+#[async_trait]
+impl EngineImpl for Timezone {
+    fn id(&self) -> &'static str {
+        Engine::Timezone.id()
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+}
+
 pub async fn request(query: &str) -> EngineResponse {
     match evaluate(query) {
         None => EngineResponse::new(),
@@ -1,9 +1,25 @@
 use maud::html;
 
-use crate::engines::{EngineResponse, SearchQuery};
+use crate::engines::{
+    async_trait, Engine, EngineImpl, EngineResponse, IntoRequestResponseResult, RequestResponse,
+    SearchQuery,
+};
 
 use super::regex;
 
+pub struct Useragent;
+
+#[async_trait]
+impl EngineImpl for Useragent {
+    fn id(&self) -> &'static str {
+        Engine::Useragent.id()
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+}
+
 pub async fn request(query: &SearchQuery) -> EngineResponse {
     if !regex!("^(what('s|s| is) my (user ?agent|ua)|ua|user ?agent)$")
         .is_match(&query.query.to_lowercase())
@@ -4,10 +4,30 @@ use maud::html;
 use serde::Deserialize;
 use url::Url;
 
-use crate::engines::{EngineResponse, RequestResponse, CLIENT};
+use crate::engines::{
+    async_trait, Engine, EngineImpl, EngineResponse, HttpResponse, IntoRequestResponseResult,
+    RequestResponse, SearchQuery, CLIENT,
+};
 
 use super::colorpicker;
 
+pub struct Wikipedia;
+
+#[async_trait]
+impl EngineImpl for Wikipedia {
+    fn id(&self) -> &'static str {
+        Engine::Wikipedia.id()
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+
+    fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        parse_response(res.into())
+    }
+}
+
 pub async fn request(mut query: &str) -> RequestResponse {
     if !colorpicker::MatchedColorModel::new(query).is_empty() {
         // "color picker" is a wikipedia article but we only want to show the
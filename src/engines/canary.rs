@@ -0,0 +1,70 @@
+//! Regularly searching for canary queries in the background, so we notice when
+//! an engine breaks before our users do.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use futures::future::join_all;
+use tracing::{info, warn};
+
+use super::{health, request_engine, Engine, SearchQuery};
+use crate::{config::Config, metrics};
+
+/// Start checking the engines in the background. This does nothing if canaries
+/// are disabled.
+pub fn spawn(config: Arc<Config>) {
+    if !config.canary.enabled || config.canary.queries.is_empty() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(config.canary.interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            check_engines(&config).await;
+        }
+    });
+}
+
+async fn check_engines(config: &Arc<Config>) {
+    let engines = config
+        .canary
+        .engines
+        .iter()
+        .copied()
+        .filter(|&engine| config.engines.get(engine).enabled)
+        .collect::<Vec<_>>();
+
+    // an engine is degraded if any of the queries fail
+    let mut failures = HashMap::<Engine, String>::new();
+    for query in &config.canary.queries {
+        let query = SearchQuery::new(query.clone(), config.clone());
+        let query = &query;
+        let results = join_all(engines.iter().map(|&engine| async move {
+            (engine, request_engine(engine, query, &|_, _| {}).await)
+        }))
+        .await;
+
+        for (engine, result) in results {
+            let failure = match result {
+                Ok(response) if response.search_results.is_empty() => {
+                    format!("no results for {:?}", query.query)
+                }
+                Ok(_) => continue,
+                Err(err) => format!("error for {:?}: {err}", query.query),
+            };
+            failures.entry(engine).or_insert(failure);
+        }
+    }
+
+    for engine in engines {
+        let failure = failures.remove(&engine);
+        metrics::set_engine_degraded(engine, failure.is_some());
+        let was_degraded = health::set_degraded(engine, failure.clone()).is_some();
+        match failure {
+            Some(failure) => warn!("{engine} is degraded, canary query got {failure}"),
+            None if was_degraded => info!("{engine} is no longer degraded"),
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Skipping engines that keep failing, so broken engines don't slow down every
+//! search.
+
+use std::{
+    collections::HashMap,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+use super::{Engine, EngineProgressUpdate};
+use crate::config::CircuitBreakerConfig;
+
+static CIRCUITS: LazyLock<Mutex<HashMap<Engine, Circuit>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Default)]
+struct Circuit {
+    consecutive_failures: u32,
+    /// When the cooldown ends. This is `None` while the circuit is closed.
+    open_until: Option<Instant>,
+    /// When the request that's checking whether the engine works again was
+    /// started, if one has been let through since the cooldown ended.
+    probe_started: Option<Instant>,
+}
+
+/// Record a progress update from an engine. Only `Done` and `Error` updates
+/// matter here.
+pub fn record_progress_update(
+    engine: Engine,
+    update: &EngineProgressUpdate,
+    config: &CircuitBreakerConfig,
+) {
+    let mut circuits = CIRCUITS.lock();
+    let circuit = circuits.entry(engine).or_default();
+    match update {
+        EngineProgressUpdate::Done => {
+            if circuit.open_until.is_some() {
+                info!("{engine} is working again");
+            }
+            *circuit = Circuit::default();
+        }
+        EngineProgressUpdate::Error(_) => {
+            circuit.consecutive_failures += 1;
+            circuit.probe_started = None;
+            if config.enabled && circuit.consecutive_failures >= config.failure_threshold {
+                warn!(
+                    "{engine} failed {} times in a row, skipping it for {}s",
+                    circuit.consecutive_failures, config.cooldown_secs
+                );
+                circuit.open_until =
+                    Some(Instant::now() + Duration::from_secs(config.cooldown_secs));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether we should skip making a request to the engine because it's been
+/// failing. After the cooldown, a single request is let through to check if
+/// the engine works again, and everything else is skipped until it finishes.
+/// If it fails then the cooldown starts over.
+pub fn should_skip(engine: Engine, config: &CircuitBreakerConfig) -> bool {
+    if !config.enabled {
+        return false;
+    }
+    let mut circuits = CIRCUITS.lock();
+    let Some(circuit) = circuits.get_mut(&engine) else {
+        return false;
+    };
+    let Some(open_until) = circuit.open_until else {
+        return false;
+    };
+    let now = Instant::now();
+    if now < open_until {
+        return true;
+    }
+    // if the probe never finished (like if the search was cancelled), another one
+    // is let through after the cooldown
+    let cooldown = Duration::from_secs(config.cooldown_secs);
+    if circuit
+        .probe_started
+        .is_some_and(|started| now.duration_since(started) < cooldown)
+    {
+        return true;
+    }
+    circuit.probe_started = Some(now);
+    false
+}
+
+/// Whether the engine is being skipped, for the stats page and readiness
+/// checks. Unlike [`should_skip`], this doesn't let a probe through.
+pub fn is_open(engine: Engine, config: &CircuitBreakerConfig) -> bool {
+    config.enabled
+        && CIRCUITS
+            .lock()
+            .get(&engine)
+            .is_some_and(|circuit| circuit.open_until.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_probe_after_cooldown() {
+        let config = CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 2,
+            cooldown_secs: 0,
+        };
+        let engine = Engine::Yep;
+        let error = EngineProgressUpdate::Error("oops".to_string());
+
+        record_progress_update(engine, &error, &config);
+        assert!(!should_skip(engine, &config));
+        record_progress_update(engine, &error, &config);
+        assert!(is_open(engine, &config));
+
+        // the cooldown is over, so one request is let through
+        let config = CircuitBreakerConfig {
+            cooldown_secs: 60,
+            ..config
+        };
+        assert!(!should_skip(engine, &config));
+        assert!(should_skip(engine, &config));
+
+        record_progress_update(engine, &EngineProgressUpdate::Done, &config);
+        assert!(!is_open(engine, &config));
+        assert!(!should_skip(engine, &config));
+    }
+}
@@ -0,0 +1,227 @@
+//! Engines that scrape a results page with CSS selectors, like the built-in
+//! search engines do with [`ParseOpts`].
+
+use std::sync::OnceLock;
+
+use eyre::eyre;
+use scraper::Selector;
+use serde::Deserialize;
+
+use super::RequestTemplate;
+use crate::{
+    engines::{
+        async_trait, EngineImpl, EngineResponse, HttpResponse, RequestResponse, SearchQuery,
+    },
+    parse::{parse_html_response_with_opts, ParseOpts, QueryMethod},
+};
+
+#[derive(Deserialize, Debug)]
+pub struct HtmlEngineConfig {
+    #[serde(flatten)]
+    pub request: RequestTemplate,
+    /// The selector for every search result.
+    pub result: String,
+    pub title: Extract,
+    pub href: Extract,
+    pub description: Extract,
+    pub featured_snippet: Option<FeaturedSnippetConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FeaturedSnippetConfig {
+    pub selector: String,
+    pub title: Extract,
+    pub href: Extract,
+    pub description: Extract,
+}
+
+/// How a value is taken from inside of a result.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Extract {
+    /// The text of the first element that matches the selector. For `href`,
+    /// this is the element's `href` attribute if it has one.
+    Text(String),
+    /// An attribute of the first element that matches the selector.
+    Attribute {
+        selector: String,
+        attribute: String,
+        /// Set by [`HtmlEngine::new`] when the selector is checked.
+        #[serde(skip)]
+        parsed_selector: OnceLock<Selector>,
+    },
+}
+
+impl Extract {
+    fn selector(&self) -> &str {
+        match self {
+            Extract::Text(selector) | Extract::Attribute { selector, .. } => selector,
+        }
+    }
+
+    fn parse_selector(&self, engine_id: &str) -> eyre::Result<()> {
+        let selector = self.selector();
+        let parsed = Selector::parse(selector)
+            .map_err(|err| eyre!("invalid selector {selector:?} in engine {engine_id}: {err}"))?;
+        if let Extract::Attribute {
+            parsed_selector, ..
+        } = self
+        {
+            let _ = parsed_selector.set(parsed);
+        }
+        Ok(())
+    }
+
+    fn query_method(&'static self) -> QueryMethod {
+        match self {
+            Extract::Text(selector) => QueryMethod::CssSelector(selector),
+            Extract::Attribute {
+                attribute,
+                parsed_selector,
+                ..
+            } => {
+                let selector = parsed_selector
+                    .get()
+                    .expect("selectors are parsed when the engine is made");
+                QueryMethod::Manual(Box::new(move |el| {
+                    Ok(el
+                        .select(selector)
+                        .next()
+                        .and_then(|n| n.value().attr(attribute))
+                        .unwrap_or_default()
+                        .to_string())
+                }))
+            }
+        }
+    }
+}
+
+pub struct HtmlEngine {
+    id: &'static str,
+    // ParseOpts wants static strings, and engines live forever anyways
+    config: &'static HtmlEngineConfig,
+}
+
+impl HtmlEngine {
+    /// Check the selectors and make the engine.
+    pub fn new(id: &'static str, config: HtmlEngineConfig) -> eyre::Result<Self> {
+        let mut selectors = vec![config.result.as_str()];
+        let mut extracts = vec![&config.title, &config.href, &config.description];
+        if let Some(featured_snippet) = &config.featured_snippet {
+            selectors.push(&featured_snippet.selector);
+            extracts.extend([
+                &featured_snippet.title,
+                &featured_snippet.href,
+                &featured_snippet.description,
+            ]);
+        }
+        for selector in selectors {
+            Selector::parse(selector)
+                .map_err(|err| eyre!("invalid selector {selector:?} in engine {id}: {err}"))?;
+        }
+        for extract in extracts {
+            extract.parse_selector(id)?;
+        }
+
+        Ok(Self {
+            id,
+            config: Box::leak(Box::new(config)),
+        })
+    }
+
+    fn parse_opts(&self) -> ParseOpts {
+        let config = self.config;
+        let opts = ParseOpts::new()
+            .result(&config.result)
+            .title(config.title.query_method())
+            .href(config.href.query_method())
+            .description(config.description.query_method());
+        match &config.featured_snippet {
+            Some(featured_snippet) => opts
+                .featured_snippet(&featured_snippet.selector)
+                .featured_snippet_title(featured_snippet.title.query_method())
+                .featured_snippet_href(featured_snippet.href.query_method())
+                .featured_snippet_description(featured_snippet.description.query_method()),
+            None => opts,
+        }
+    }
+}
+
+#[async_trait]
+impl EngineImpl for HtmlEngine {
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn is_search_engine(&self) -> bool {
+        true
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        Ok(self.config.request.request(query).into())
+    }
+
+    fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        parse_html_response_with_opts(&res.body, self.parse_opts())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_html_engine_response() {
+        let config: HtmlEngineConfig = toml::from_str(
+            r#"
+            url = "https://example.com/search?q={query}&p={page}"
+            result = "li.result"
+            title = "h2"
+            href = { selector = "a.link", attribute = "data-url" }
+            description = "p"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.request.url("rust lang"),
+            "https://example.com/search?q=rust%20lang&p=1"
+        );
+
+        let engine = HtmlEngine::new("example", config).unwrap();
+        let response = parse_html_response_with_opts(
+            r#"<ul>
+                <li class="result"><h2>Rust</h2><a class="link" data-url="https://rust-lang.org/">x</a><p>A language</p></li>
+                <li class="result"><h2>No description</h2><a class="link" data-url="https://example.com/"></a></li>
+            </ul>"#,
+            engine.parse_opts(),
+        )
+        .unwrap();
+        assert_eq!(response.search_results.len(), 1);
+        assert_eq!(response.search_results[0].title, "Rust");
+        assert_eq!(response.search_results[0].url, "https://rust-lang.org");
+        assert_eq!(response.search_results[0].description, "A language");
+
+        let broken_config: HtmlEngineConfig = toml::from_str(
+            r#"
+            url = "https://example.com/search?q={query}"
+            result = "li["
+            title = "h2"
+            href = "a"
+            description = "p"
+            "#,
+        )
+        .unwrap();
+        assert!(HtmlEngine::new("broken", broken_config).is_err());
+        let broken_config: HtmlEngineConfig = toml::from_str(
+            r#"
+            url = "https://example.com/search?q={query}"
+            result = "li"
+            title = "h2"
+            href = { selector = "a[", attribute = "href" }
+            description = "p"
+            "#,
+        )
+        .unwrap();
+        assert!(HtmlEngine::new("broken", broken_config).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+//! Engines that call an API which returns JSON, with paths that say where the
+//! results are.
+
+use maud::html;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::RequestTemplate;
+use crate::{
+    engines::{
+        async_trait, EngineImpl, EngineResponse, EngineSearchResult, HttpResponse, RequestResponse,
+        SearchQuery,
+    },
+    urls::normalize_url,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct JsonEngineConfig {
+    #[serde(flatten)]
+    pub request: RequestTemplate,
+    /// The array of results in the response.
+    pub results: JsonPath,
+    /// Where each field is, relative to a result.
+    pub fields: JsonFields,
+    /// Text that's shown as an answer, relative to the whole response.
+    pub answer: Option<JsonPath>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct JsonFields {
+    pub url: JsonPath,
+    pub title: JsonPath,
+    pub description: Option<JsonPath>,
+}
+
+/// A JSON pointer like `/data/items/0`, or a simple JSONPath like
+/// `$.data.items[0]` (which is converted to a pointer).
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct JsonPath(String);
+
+impl TryFrom<String> for JsonPath {
+    type Error = String;
+
+    fn try_from(path: String) -> Result<Self, Self::Error> {
+        if path.is_empty() || path.starts_with('/') {
+            return Ok(JsonPath(path));
+        }
+        let Some(mut rest) = path.strip_prefix('$') else {
+            return Err(format!(
+                "{path:?} should be a JSON pointer (starting with /) or a JSONPath (starting with $)"
+            ));
+        };
+
+        let mut pointer = String::new();
+        while !rest.is_empty() {
+            let segment;
+            if let Some(after_dot) = rest.strip_prefix('.') {
+                let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+                (segment, rest) = after_dot.split_at(end);
+            } else if let Some(after_bracket) = rest.strip_prefix('[') {
+                let end = after_bracket
+                    .find(']')
+                    .ok_or_else(|| format!("unclosed bracket in {path:?}"))?;
+                let key = &after_bracket[..end];
+                segment = key
+                    .strip_prefix('\'')
+                    .and_then(|key| key.strip_suffix('\''))
+                    .unwrap_or(key);
+                rest = &after_bracket[end + 1..];
+            } else {
+                return Err(format!("unexpected {rest:?} in {path:?}"));
+            }
+            if segment.is_empty() || segment == "*" {
+                return Err(format!(
+                    "{path:?} isn't supported, only JSONPaths with names and indexes are"
+                ));
+            }
+            pointer.push('/');
+            pointer.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+        }
+        Ok(JsonPath(pointer))
+    }
+}
+
+impl JsonPath {
+    fn get<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        value.pointer(&self.0)
+    }
+
+    /// The value as a string, or `None` if it's missing, null, or empty.
+    fn get_string(&self, value: &Value) -> Option<String> {
+        let s = match self.get(value)? {
+            Value::Null => return None,
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        (!s.is_empty()).then_some(s)
+    }
+}
+
+pub struct JsonEngine {
+    id: &'static str,
+    config: JsonEngineConfig,
+}
+
+impl JsonEngine {
+    #[must_use]
+    pub fn new(id: &'static str, config: JsonEngineConfig) -> Self {
+        Self { id, config }
+    }
+
+    fn parse_body(&self, body: &str) -> eyre::Result<EngineResponse> {
+        let json: Value = serde_json::from_str(body)?;
+
+        // a missing results array is treated as no results, since some APIs leave
+        // it out instead of returning an empty one
+        let results = self
+            .config
+            .results
+            .get(&json)
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let fields = &self.config.fields;
+        let search_results = results
+            .iter()
+            .filter_map(|result| {
+                Some(EngineSearchResult {
+                    url: normalize_url(&fields.url.get_string(result)?),
+                    title: fields.title.get_string(result)?,
+                    description: fields
+                        .description
+                        .as_ref()
+                        .and_then(|description| description.get_string(result))
+                        .unwrap_or_default(),
+                })
+            })
+            .collect();
+
+        let answer_html = self
+            .config
+            .answer
+            .as_ref()
+            .and_then(|answer| answer.get_string(&json))
+            .map(|answer| html! { p { (answer) } });
+
+        Ok(EngineResponse {
+            search_results,
+            answer_html,
+            ..Default::default()
+        })
+    }
+}
+
+#[async_trait]
+impl EngineImpl for JsonEngine {
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn is_search_engine(&self) -> bool {
+        true
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        Ok(self.config.request.request(query).into())
+    }
+
+    fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        self.parse_body(&res.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use axum::{extract::Query, http::HeaderMap, routing::post, Json, Router};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        engines::{make_request, register},
+    };
+
+    fn path(s: &str) -> Result<JsonPath, String> {
+        JsonPath::try_from(s.to_string())
+    }
+
+    #[test]
+    fn test_json_path() {
+        assert_eq!(path("/a/0"), Ok(JsonPath("/a/0".to_string())));
+        assert_eq!(path("$"), Ok(JsonPath(String::new())));
+        assert_eq!(
+            path("$.data.items[0]['a/b']"),
+            Ok(JsonPath("/data/items/0/a~1b".to_string()))
+        );
+        assert!(path("$.items[*].url").is_err());
+        assert!(path("items").is_err());
+    }
+
+    #[test]
+    fn test_parse_json_engine_response() {
+        let config: JsonEngineConfig = toml::from_str(
+            r#"
+            url = "http://127.0.0.1:8080/search"
+            method = "POST"
+            body = '{"q": "{query}"}'
+            results = "$.data.hits"
+            answer = "/summary"
+            fields = { url = "/link", title = "$.title", description = "/snippet" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.request.body(r#"say "hi""#).unwrap(),
+            r#"{"q": "say \"hi\""}"#
+        );
+
+        let engine = JsonEngine::new("example", config);
+        let response = engine
+            .parse_body(
+                r#"{
+                    "summary": "Rust is a <language>",
+                    "data": {
+                        "hits": [
+                            { "link": "https://www.rust-lang.org/learn", "title": "Learn Rust", "snippet": null },
+                            { "title": "No link" }
+                        ]
+                    }
+                }"#,
+            )
+            .unwrap();
+        assert_eq!(response.search_results.len(), 1);
+        assert_eq!(response.search_results[0].title, "Learn Rust");
+        assert_eq!(response.search_results[0].description, "");
+        assert_eq!(
+            response.answer_html.unwrap().0,
+            "<p>Rust is a &lt;language&gt;</p>"
+        );
+
+        assert!(engine.parse_body("{}").unwrap().search_results.is_empty());
+        assert!(engine.parse_body("not json").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_json_engine_against_server() {
+        let app = Router::new().route(
+            "/search",
+            post(
+                |Query(params): Query<HashMap<String, String>>,
+                 headers: HeaderMap,
+                 Json(body): Json<Value>| async move {
+                    assert_eq!(params["lang"], "en");
+                    assert_eq!(headers["x-api-key"], "secret");
+                    Json(json!({
+                        "results": [
+                            {
+                                "url": format!("https://example.com/{}", params["q"]),
+                                "title": body["query"],
+                                "snippet": "A <b>result</b>"
+                            }
+                        ]
+                    }))
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config: JsonEngineConfig = toml::from_str(&format!(
+            r#"
+            url = "http://{addr}/search?q={{query}}&lang=en"
+            method = "POST"
+            headers = {{ x-api-key = "secret" }}
+            body = '{{"query": "{{query}}"}}'
+            results = "$.results"
+            fields = {{ url = "/url", title = "/title", description = "/snippet" }}
+            "#
+        ))
+        .unwrap();
+        let engine = register(JsonEngine::new("test_json_server", config));
+        let query = SearchQuery::new("rust \"lang\"", Arc::new(Config::default()));
+
+        let RequestResponse::Http(request) = engine.request(&query).await.unwrap() else {
+            panic!("expected an http request");
+        };
+        let res = make_request(*request, engine, &query, |_, _| {})
+            .await
+            .unwrap();
+        let response = engine.parse_response(&res).unwrap();
+
+        assert_eq!(response.search_results.len(), 1);
+        let result = &response.search_results[0];
+        assert_eq!(result.url, "https://example.com/rust%20%22lang%22");
+        assert_eq!(result.title, r#"rust "lang""#);
+        assert_eq!(result.description, "A <b>result</b>");
+    }
+}
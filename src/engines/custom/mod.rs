@@ -0,0 +1,129 @@
+//! Engines that are defined in the `[custom_engines]` section of the config
+//! instead of in code.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use eyre::bail;
+use serde::Deserialize;
+
+use super::{builtin_engines, register, Engine, CLIENT};
+
+mod html;
+mod json;
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum CustomEngineConfig {
+    Html(Box<html::HtmlEngineConfig>),
+    Json(Box<json::JsonEngineConfig>),
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct PartialCustomEnginesConfig {
+    #[serde(default)]
+    custom_engines: BTreeMap<String, CustomEngineConfig>,
+}
+
+/// The request that's made for every search.
+#[derive(Deserialize, Debug)]
+pub(crate) struct RequestTemplate {
+    /// `{query}` is replaced with the URL-encoded query, and `{page}` with the
+    /// page number (which is always 1 for now).
+    pub url: String,
+    #[serde(default)]
+    pub method: RequestMethod,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// `{query}` is replaced with the query escaped as a JSON string (without
+    /// the quotes).
+    pub body: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub(crate) enum RequestMethod {
+    #[default]
+    Get,
+    Post,
+}
+
+impl RequestTemplate {
+    #[must_use]
+    pub fn url(&self, query: &str) -> String {
+        self.url
+            .replace("{query}", &urlencoding::encode(query))
+            .replace("{page}", "1")
+    }
+
+    #[must_use]
+    pub fn body(&self, query: &str) -> Option<String> {
+        let escaped_query = serde_json::to_string(query).unwrap_or_default();
+        let escaped_query = &escaped_query[1..escaped_query.len() - 1];
+        Some(self.body.as_ref()?.replace("{query}", escaped_query))
+    }
+
+    pub fn request(&self, query: &str) -> wreq::RequestBuilder {
+        let url = self.url(query);
+        let mut request = match self.method {
+            RequestMethod::Get => CLIENT.get(url),
+            RequestMethod::Post => CLIENT.post(url),
+        };
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        if let Some(body) = self.body(query) {
+            if !self
+                .headers
+                .keys()
+                .any(|name| name.eq_ignore_ascii_case("content-type"))
+            {
+                request = request.header("content-type", "application/json");
+            }
+            request = request.body(body);
+        }
+        request
+    }
+}
+
+/// Register the engines from the `[custom_engines]` section of the config
+/// file. This has to happen before the config is read, since the `[engines]`
+/// section can refer to them.
+///
+/// Engines can't be changed once they're registered, so ids that were
+/// registered by an earlier call are skipped.
+pub fn register_from_file(config_path: &Path) -> eyre::Result<()> {
+    if !config_path.exists() {
+        return Ok(());
+    }
+    register_from_config(&fs::read_to_string(config_path)?)
+}
+
+/// Like [`register_from_file`], but with the contents of the config.
+pub fn register_from_config(config_str: &str) -> eyre::Result<()> {
+    let config: PartialCustomEnginesConfig = toml::from_str(config_str)?;
+    let builtin_ids = builtin_engines()
+        .iter()
+        .map(|engine| engine.id())
+        .collect::<Vec<_>>();
+
+    for (id, engine_config) in config.custom_engines {
+        if builtin_ids.contains(&id.as_str()) {
+            bail!("custom engine {id} has the same id as a built-in engine");
+        }
+        if Engine::from_str(&id).is_ok() {
+            continue;
+        }
+        // this only happens once per engine, so leaking it is fine
+        let id: &'static str = Box::leak(id.into_boxed_str());
+        match engine_config {
+            CustomEngineConfig::Html(config) => register(html::HtmlEngine::new(id, *config)?),
+            CustomEngineConfig::Json(config) => register(json::JsonEngine::new(id, *config)),
+        };
+    }
+    Ok(())
+}
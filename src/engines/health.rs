@@ -0,0 +1,210 @@
+//! Keeping track of how well each engine has been working recently, for the
+//! stats page.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use super::{circuit_breaker, Engine, EngineProgressUpdate};
+use crate::config::CircuitBreakerConfig;
+
+static HEALTH: LazyLock<Mutex<HashMap<Engine, EngineHealth>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Events older than this are forgotten.
+const MAX_EVENT_AGE: Duration = Duration::from_secs(60 * 60 * 24);
+/// The maximum number of events that are kept for each engine, so popular
+/// instances don't use too much memory.
+const MAX_EVENTS: usize = 10_000;
+
+/// The windows that are shown on the stats page, along with their names.
+pub const WINDOWS: &[(&str, Duration)] = &[
+    ("5m", Duration::from_secs(60 * 5)),
+    ("1h", Duration::from_secs(60 * 60)),
+    ("24h", MAX_EVENT_AGE),
+];
+
+#[derive(Default)]
+struct EngineHealth {
+    /// Whether each request succeeded, and how long it took if it did.
+    requests: VecDeque<(Instant, Option<u64>)>,
+    results: VecDeque<(Instant, usize)>,
+    last_error: Option<(String, DateTime<Utc>)>,
+    /// Why the last canary check failed, if it did.
+    degraded: Option<String>,
+}
+
+impl EngineHealth {
+    fn prune(&mut self) {
+        let now = Instant::now();
+        let is_old = |at: &Instant| now.duration_since(*at) > MAX_EVENT_AGE;
+        while self.requests.front().is_some_and(|(at, _)| is_old(at))
+            || self.requests.len() > MAX_EVENTS
+        {
+            self.requests.pop_front();
+        }
+        while self.results.front().is_some_and(|(at, _)| is_old(at))
+            || self.results.len() > MAX_EVENTS
+        {
+            self.results.pop_front();
+        }
+    }
+}
+
+/// Record a progress update from an engine. Only `Done` and `Error` updates
+/// matter here.
+pub fn record_progress_update(engine: Engine, update: &EngineProgressUpdate, time_ms: u64) {
+    let mut health = HEALTH.lock();
+    let engine_health = health.entry(engine).or_default();
+    match update {
+        EngineProgressUpdate::Done => {
+            engine_health
+                .requests
+                .push_back((Instant::now(), Some(time_ms)));
+        }
+        EngineProgressUpdate::Error(message) => {
+            engine_health.requests.push_back((Instant::now(), None));
+            engine_health.last_error = Some((message.clone(), Utc::now()));
+        }
+        _ => return,
+    }
+    engine_health.prune();
+}
+
+pub fn record_results(engine: Engine, count: usize) {
+    let mut health = HEALTH.lock();
+    let engine_health = health.entry(engine).or_default();
+    engine_health.results.push_back((Instant::now(), count));
+    engine_health.prune();
+}
+
+/// Set whether the engine is degraded, returning the previous reason.
+pub fn set_degraded(engine: Engine, reason: Option<String>) -> Option<String> {
+    let mut health = HEALTH.lock();
+    std::mem::replace(&mut health.entry(engine).or_default().degraded, reason)
+}
+
+/// Whether the engine failed its last canary check.
+pub fn is_degraded(engine: Engine) -> bool {
+    HEALTH
+        .lock()
+        .get(&engine)
+        .is_some_and(|h| h.degraded.is_some())
+}
+
+/// Whether the engine is degraded or its circuit breaker is open.
+pub fn is_failing(engine: Engine, circuit_breaker: &CircuitBreakerConfig) -> bool {
+    is_degraded(engine) || circuit_breaker::is_open(engine, circuit_breaker)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineHealthSummary {
+    pub engine: Engine,
+    pub windows: Vec<WindowSummary>,
+    pub last_error: Option<String>,
+    /// In RFC 3339 format.
+    pub last_error_time: Option<String>,
+    pub circuit_open: bool,
+    /// Why the engine failed its last canary check, if it did.
+    pub degraded: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowSummary {
+    pub window: &'static str,
+    pub requests: usize,
+    /// From 0 to 1. This is `None` if no requests were made.
+    pub success_rate: Option<f64>,
+    pub median_latency_ms: Option<u64>,
+    pub p95_latency_ms: Option<u64>,
+    pub average_results: Option<f64>,
+}
+
+/// Summarize the health of every engine that's been used recently.
+pub fn summary(circuit_breaker: &CircuitBreakerConfig) -> Vec<EngineHealthSummary> {
+    let now = Instant::now();
+    let mut health = HEALTH.lock();
+    let mut summaries = health
+        .iter_mut()
+        .map(|(&engine, engine_health)| {
+            engine_health.prune();
+            let windows = WINDOWS
+                .iter()
+                .map(|&(window, duration)| {
+                    let in_window = |at: &Instant| now.duration_since(*at) <= duration;
+                    let requests = engine_health
+                        .requests
+                        .iter()
+                        .filter(|(at, _)| in_window(at))
+                        .collect::<Vec<_>>();
+                    let mut latencies = requests
+                        .iter()
+                        .filter_map(|(_, latency)| *latency)
+                        .collect::<Vec<_>>();
+                    latencies.sort_unstable();
+                    let results = engine_health
+                        .results
+                        .iter()
+                        .filter(|(at, _)| in_window(at))
+                        .map(|(_, count)| *count)
+                        .collect::<Vec<_>>();
+
+                    WindowSummary {
+                        window,
+                        requests: requests.len(),
+                        success_rate: (!requests.is_empty())
+                            .then(|| latencies.len() as f64 / requests.len() as f64),
+                        median_latency_ms: percentile(&latencies, 0.5),
+                        p95_latency_ms: percentile(&latencies, 0.95),
+                        average_results: (!results.is_empty())
+                            .then(|| results.iter().sum::<usize>() as f64 / results.len() as f64),
+                    }
+                })
+                .collect();
+            let (last_error, last_error_time) = engine_health
+                .last_error
+                .clone()
+                .map(|(message, time)| (message, time.to_rfc3339()))
+                .unzip();
+            EngineHealthSummary {
+                engine,
+                windows,
+                last_error,
+                last_error_time,
+                circuit_open: circuit_breaker::is_open(engine, circuit_breaker),
+                degraded: engine_health.degraded.clone(),
+            }
+        })
+        .collect::<Vec<_>>();
+    summaries.sort_by_key(|s| s.engine);
+    summaries
+}
+
+/// Nearest-rank percentile of a sorted list.
+fn percentile(sorted: &[u64], p: f64) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let latencies = (1..=100).collect::<Vec<_>>();
+        assert_eq!(percentile(&latencies, 0.5), Some(50));
+        assert_eq!(percentile(&latencies, 0.95), Some(95));
+        assert_eq!(percentile(&[7], 0.95), Some(7));
+        assert_eq!(percentile(&[], 0.5), None);
+    }
+}
@@ -1,3 +1,5 @@
+//! Running searches with every enabled engine and merging their results.
+
 use std::{
     collections::{BTreeSet, HashMap},
     fmt::{self, Display},
@@ -8,100 +10,67 @@ use std::{
     time::{Duration, Instant},
 };
 
-use eyre::bail;
-use futures::future::join_all;
+use async_stream::stream;
+use eyre::{bail, eyre};
+use futures::{future::join_all, Stream};
 use maud::PreEscaped;
+use parking_lot::Mutex;
 use serde::{Deserialize, Deserializer, Serialize};
 use tokio::sync::mpsc;
-use tracing::{error, info};
+use tracing::{error, info, info_span, Instrument, Span};
 use wreq_util::Emulation;
 
-mod macros;
+pub(crate) mod canary;
+pub(crate) mod circuit_breaker;
+pub mod custom;
+pub(crate) mod health;
 mod ranking;
-use crate::{
-    config::Config, engine_autocomplete_requests, engine_image_requests,
-    engine_postsearch_requests, engine_requests, engines,
-};
+mod registry;
+use crate::{config::Config, metrics};
 
+// the built-in engines are used through the registry, so they're not part of
+// the library's api
+#[doc(hidden)]
 pub mod answer;
+#[doc(hidden)]
 pub mod postsearch;
+#[doc(hidden)]
 pub mod search;
 
-engines! {
-    // search
-    Google = "google",
-    GoogleScholar = "google_scholar",
-    Bing = "bing",
-    Brave = "brave",
-    Marginalia = "marginalia",
-    RightDao = "rightdao",
-    Stract = "stract",
-    Yep = "yep",
-    // answer
-    Dictionary = "dictionary",
-    Fend = "fend",
-    Ip = "ip",
-    Notepad = "notepad",
-    ColorPicker = "colorpicker",
-    Numbat = "numbat",
-    Thesaurus = "thesaurus",
-    Timezone = "timezone",
-    Useragent = "useragent",
-    Wikipedia = "wikipedia",
-    // post-search
-    DocsRs = "docs_rs",
-    GitHub = "github",
-    Mdn = "mdn",
-    MinecraftWiki = "minecraft_wiki",
-    StackExchange = "stackexchange",
-}
-
-engine_requests! {
-    // search
-    Bing => search::bing::request, parse_response,
-    Brave => search::brave::request, parse_response,
-    GoogleScholar => search::google_scholar::request, parse_response,
-    Google => search::google::request, parse_response,
-    Marginalia => search::marginalia::request, parse_response,
-    RightDao => search::rightdao::request, parse_response,
-    Stract => search::stract::request, parse_response,
-    Yep => search::yep::request, parse_response,
-    // answer
-    Dictionary => answer::dictionary::request, parse_response,
-    Fend => answer::fend::request, None,
-    Ip => answer::ip::request, None,
-    Notepad => answer::notepad::request, None,
-    ColorPicker => answer::colorpicker::request, None,
-    Numbat => answer::numbat::request, None,
-    Thesaurus => answer::thesaurus::request, parse_response,
-    Timezone => answer::timezone::request, None,
-    Useragent => answer::useragent::request, None,
-    Wikipedia => answer::wikipedia::request, parse_response,
-}
-
-engine_autocomplete_requests! {
-    Google => search::google::request_autocomplete, parse_autocomplete_response,
-    Fend => answer::fend::request_autocomplete, None,
-    Numbat => answer::numbat::request_autocomplete, None,
-}
-
-engine_postsearch_requests! {
-    DocsRs => postsearch::docs_rs::request, parse_response,
-    GitHub => postsearch::github::request, parse_response,
-    Mdn => postsearch::mdn::request, parse_response,
-    MinecraftWiki => postsearch::minecraft_wiki::request, parse_response,
-    StackExchange => postsearch::stackexchange::request, parse_response,
-}
-
-engine_image_requests! {
-    Google => search::google::request_images, parse_images_response,
-    Bing => search::bing::request_images, parse_images_response,
-}
-
-impl fmt::Display for Engine {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.id())
-    }
+pub use async_trait::async_trait;
+pub use registry::{options_table, register, Engine, EngineImpl};
+
+/// The engines that come with metasearch, in the order that they're
+/// registered.
+fn builtin_engines() -> Vec<Arc<dyn EngineImpl>> {
+    vec![
+        // search
+        Arc::new(search::google::Google),
+        Arc::new(search::google_scholar::GoogleScholar),
+        Arc::new(search::bing::Bing),
+        Arc::new(search::brave::Brave),
+        Arc::new(search::marginalia::Marginalia),
+        Arc::new(search::rightdao::RightDao),
+        Arc::new(search::stract::Stract),
+        Arc::new(search::yep::Yep),
+        // answer
+        Arc::new(answer::dictionary::Dictionary),
+        Arc::new(answer::fend::Fend),
+        Arc::new(answer::ip::Ip),
+        Arc::new(answer::notepad::Notepad),
+        Arc::new(answer::colorpicker::ColorPicker),
+        Arc::new(answer::numbat::Numbat),
+        Arc::new(answer::thesaurus::Thesaurus),
+        Arc::new(answer::timezone::Timezone),
+        Arc::new(answer::useragent::Useragent),
+        Arc::new(answer::wikipedia::Wikipedia),
+        // post-search
+        Arc::new(postsearch::docs_rs::DocsRs),
+        Arc::new(postsearch::github::GitHub),
+        Arc::new(postsearch::mdn::Mdn),
+        Arc::new(postsearch::minecraft_wiki::MinecraftWiki),
+        Arc::new(postsearch::stackexchange::StackExchange),
+    ]
 }
 
 impl<'de> Deserialize<'de> for Engine {
@@ -114,16 +83,41 @@ impl<'de> Deserialize<'de> for Engine {
     }
 }
 
+/// A query that's given to every engine. It derefs to the query string.
 pub struct SearchQuery {
+    /// What the user searched for.
     pub query: String,
+    /// Which kind of results are wanted.
     pub tab: SearchTab,
+    /// The headers of the request the search came from, with lowercase names.
     pub request_headers: HashMap<String, String>,
+    /// The address of whoever made the search.
     pub ip: String,
     /// The config is part of the query so it's possible to make a query with a
     /// custom config.
     pub config: Arc<Config>,
 }
 
+impl SearchQuery {
+    /// A query for the "All" tab, as if it was made from the same machine.
+    #[must_use]
+    pub fn new(query: impl Into<String>, config: Arc<Config>) -> Self {
+        Self {
+            query: query.into(),
+            tab: SearchTab::default(),
+            request_headers: HashMap::new(),
+            ip: "127.0.0.1".to_string(),
+            config,
+        }
+    }
+
+    /// The same query, but for a different tab.
+    #[must_use]
+    pub fn with_tab(self, tab: SearchTab) -> Self {
+        Self { tab, ..self }
+    }
+}
+
 impl Deref for SearchQuery {
     type Target = str;
 
@@ -132,10 +126,13 @@ impl Deref for SearchQuery {
     }
 }
 
+/// The tabs at the top of the search page.
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SearchTab {
+    /// Web results, plus answers and infoboxes.
     #[default]
     All,
+    /// Image results.
     Images,
 }
 impl FromStr for SearchTab {
@@ -157,9 +154,14 @@ impl Display for SearchTab {
     }
 }
 
+/// What an engine does for a query.
 pub enum RequestResponse {
+    /// Nothing, the engine doesn't have results for this query.
     None,
+    /// A request that's sent and then given to
+    /// [`EngineImpl::parse_response`].
     Http(Box<wreq::RequestBuilder>),
+    /// A response that the engine made by itself.
     Instant(Box<EngineResponse>),
 }
 impl From<wreq::RequestBuilder> for RequestResponse {
@@ -193,8 +195,26 @@ impl IntoRequestResponseResult for eyre::Result<RequestResponse> {
     }
 }
 
+/// Whether an engine can autocomplete queries, and how.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutocompleteKind {
+    /// The engine doesn't autocomplete.
+    #[default]
+    None,
+    /// [`EngineImpl::request_autocomplete`] returns a request that still has to
+    /// be made.
+    Http,
+    /// [`EngineImpl::request_autocomplete`] evaluates the query itself (like a
+    /// calculator does), so it's run on a blocking thread.
+    Instant,
+}
+
+/// What an engine does to autocomplete a query.
 pub enum RequestAutocompleteResponse {
+    /// A request that's sent and then given to
+    /// [`EngineImpl::parse_autocomplete_response`].
     Http(Box<wreq::RequestBuilder>),
+    /// The suggestions themselves.
     Instant(Vec<String>),
 }
 impl From<wreq::RequestBuilder> for RequestAutocompleteResponse {
@@ -208,9 +228,13 @@ impl From<Vec<String>> for RequestAutocompleteResponse {
     }
 }
 
+/// A response from an engine's website, given to the engine to parse.
 pub struct HttpResponse {
+    /// The response, with the body already read.
     pub res: wreq::Response,
+    /// The body of the response.
     pub body: String,
+    /// The config of the search that made the request.
     pub config: Arc<Config>,
 }
 
@@ -226,39 +250,56 @@ impl From<HttpResponse> for wreq::Response {
     }
 }
 
+/// A search result from a single engine.
 #[derive(Debug, Clone, Serialize)]
 pub struct EngineSearchResult {
+    /// The url of the page.
     pub url: String,
+    /// The title of the page.
     pub title: String,
+    /// A snippet of the page, as plain text.
     pub description: String,
 }
 
+/// A result that an engine shows above the others.
 #[derive(Debug)]
 pub struct EngineFeaturedSnippet {
+    /// The url of the page.
     pub url: String,
+    /// The title of the page.
     pub title: String,
+    /// A snippet of the page, as plain text.
     pub description: String,
 }
 
+/// Everything a single engine found for the "All" tab.
 #[derive(Debug, Default)]
 pub struct EngineResponse {
+    /// The results, best first.
     pub search_results: Vec<EngineSearchResult>,
+    /// A result to show above the others.
     pub featured_snippet: Option<EngineFeaturedSnippet>,
+    /// Html that answers the query directly, like a calculator result.
     pub answer_html: Option<PreEscaped<String>>,
+    /// Html about the thing being searched for, shown next to the results.
     pub infobox_html: Option<PreEscaped<String>>,
 }
 
+/// Everything a single engine found for the "Images" tab.
 #[derive(Default)]
 pub struct EngineImagesResponse {
+    /// The results, best first.
     pub image_results: Vec<EngineImageResult>,
 }
 
 impl EngineResponse {
+    /// A response with no results.
     #[must_use]
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// A response with only an answer.
     #[must_use]
     pub fn answer_html(html: PreEscaped<String>) -> Self {
         Self {
@@ -267,6 +308,7 @@ impl EngineResponse {
         }
     }
 
+    /// A response with only an infobox.
     #[must_use]
     pub fn infobox_html(html: PreEscaped<String>) -> Self {
         Self {
@@ -277,47 +319,74 @@ impl EngineResponse {
 }
 
 impl EngineImagesResponse {
+    /// A response with no results.
     #[must_use]
     pub fn new() -> Self {
         Self::default()
     }
 }
 
+/// An image result from a single engine.
 #[derive(Debug, Clone, Serialize)]
 pub struct EngineImageResult {
+    /// The url of the full size image.
     pub image_url: String,
+    /// The url of the page the image is on.
     pub page_url: String,
+    /// The title of the page or image.
     pub title: String,
+    /// The width of the image in pixels, or 0 if it's unknown.
     pub width: u64,
+    /// The height of the image in pixels, or 0 if it's unknown.
     pub height: u64,
 }
 
-#[derive(Debug)]
+/// Where an engine is at in a search.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", content = "message", rename_all = "snake_case")]
 pub enum EngineProgressUpdate {
+    /// The request was sent.
     Requesting,
+    /// The response headers came back and the body is being read.
     Downloading,
+    /// The body is being parsed.
     Parsing,
+    /// The engine has its results.
     Done,
+    /// The engine failed, with a message saying why.
     Error(String),
 }
 
-#[derive(Debug)]
+/// Something that happened during a search.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ProgressUpdateData {
+    /// An engine got further along.
     Engine {
+        /// The engine this is about.
         engine: Engine,
+        /// What it's doing now.
         update: EngineProgressUpdate,
     },
+    /// The merged results of every engine.
     Response(ResponseForTab),
+    /// An infobox from an engine that runs after the search.
     PostSearchInfobox(Infobox),
 }
 
-#[derive(Debug)]
+/// A [`ProgressUpdateData`], with when it happened.
+#[derive(Debug, Serialize)]
 pub struct ProgressUpdate {
+    /// What happened.
+    #[serde(flatten)]
     pub data: ProgressUpdateData,
+    /// How long after the start of the search it happened.
     pub time_ms: u64,
 }
 
 impl ProgressUpdate {
+    /// An update that happened now, for a search that started at
+    /// `start_time`.
     #[must_use]
     pub fn new(data: ProgressUpdateData, start_time: Instant) -> Self {
         Self {
@@ -327,6 +396,15 @@ impl ProgressUpdate {
     }
 }
 
+/// Run CPU-heavy work (like parsing HTML or evaluating math) on a blocking
+/// thread, so it doesn't hold up other requests.
+pub async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> eyre::Result<T> {
+    let span = Span::current();
+    Ok(tokio::task::spawn_blocking(move || span.in_scope(f)).await?)
+}
+
 async fn make_request(
     request: wreq::RequestBuilder,
     engine: Engine,
@@ -335,14 +413,22 @@ async fn make_request(
 ) -> eyre::Result<HttpResponse> {
     send_engine_progress_update(engine, EngineProgressUpdate::Requesting);
 
-    let mut res = request.send().await?;
+    let mut res = request
+        .send()
+        .instrument(info_span!("send", %engine))
+        .await?;
 
     send_engine_progress_update(engine, EngineProgressUpdate::Downloading);
 
-    let mut body_bytes = Vec::new();
-    while let Some(chunk) = res.chunk().await? {
-        body_bytes.extend_from_slice(&chunk);
+    let body_bytes = async {
+        let mut body_bytes = Vec::new();
+        while let Some(chunk) = res.chunk().await? {
+            body_bytes.extend_from_slice(&chunk);
+        }
+        eyre::Ok(body_bytes)
     }
+    .instrument(info_span!("download", %engine))
+    .await?;
     let body = String::from_utf8_lossy(&body_bytes).to_string();
 
     send_engine_progress_update(engine, EngineProgressUpdate::Parsing);
@@ -355,65 +441,86 @@ async fn make_request(
     Ok(http_response)
 }
 
-async fn make_requests(
+/// Make a request to an engine and parse its response, sending progress
+/// updates along the way.
+#[tracing::instrument(skip_all, fields(%engine))]
+async fn request_engine(
+    engine: Engine,
     query: &SearchQuery,
-    progress_tx: &mpsc::UnboundedSender<ProgressUpdate>,
-    start_time: Instant,
     send_engine_progress_update: &impl Fn(Engine, EngineProgressUpdate),
-) -> eyre::Result<()> {
-    let mut requests = Vec::new();
-    for &engine in Engine::all() {
-        let engine_config = query.config.engines.get(engine);
-        if !engine_config.enabled {
-            continue;
+) -> eyre::Result<EngineResponse> {
+    let request_response = match engine.request(query).await {
+        Ok(r) => r,
+        Err(e) => {
+            error!(
+                "request error for {engine}: {}",
+                query.config.logging.loggable_error(&e, &query.query)
+            );
+            send_engine_progress_update(engine, EngineProgressUpdate::Error(e.to_string()));
+            return Err(e);
         }
+    };
 
-        requests.push(async move {
-            let request_response = match engine.request(query).await {
-                Ok(r) => r,
+    let response = match request_response {
+        RequestResponse::Http(request) => {
+            let http_response =
+                match make_request(*request, engine, query, send_engine_progress_update).await {
+                    Ok(http_response) => http_response,
+                    Err(e) => {
+                        send_engine_progress_update(
+                            engine,
+                            EngineProgressUpdate::Error(e.to_string()),
+                        );
+                        return Err(e);
+                    }
+                };
+
+            let parse_result = run_blocking(move || engine.parse_response(&http_response))
+                .await
+                .and_then(|r| r);
+            let response = match parse_result {
+                Ok(response) => response,
                 Err(e) => {
-                    error!("request error for {engine}: {e}");
+                    error!(
+                        "parse error for {engine}: {}",
+                        query.config.logging.loggable_error(&e, &query.query)
+                    );
                     send_engine_progress_update(engine, EngineProgressUpdate::Error(e.to_string()));
                     return Err(e);
                 }
             };
 
-            let response = match request_response {
-                RequestResponse::Http(request) => {
-                    let http_response =
-                        match make_request(*request, engine, query, send_engine_progress_update)
-                            .await
-                        {
-                            Ok(http_response) => http_response,
-                            Err(e) => {
-                                send_engine_progress_update(
-                                    engine,
-                                    EngineProgressUpdate::Error(e.to_string()),
-                                );
-                                return Err(e);
-                            }
-                        };
+            send_engine_progress_update(engine, EngineProgressUpdate::Done);
 
-                    let response = match engine.parse_response(&http_response) {
-                        Ok(response) => response,
-                        Err(e) => {
-                            error!("parse error for {engine}: {e}");
-                            send_engine_progress_update(
-                                engine,
-                                EngineProgressUpdate::Error(e.to_string()),
-                            );
-                            return Err(e);
-                        }
-                    };
+            response
+        }
+        RequestResponse::Instant(response) => *response,
+        RequestResponse::None => EngineResponse::new(),
+    };
 
-                    send_engine_progress_update(engine, EngineProgressUpdate::Done);
+    Ok(response)
+}
 
-                    response
-                }
-                RequestResponse::Instant(response) => *response,
-                RequestResponse::None => EngineResponse::new(),
-            };
+async fn make_requests(
+    query: &SearchQuery,
+    engines: &[Engine],
+    progress_tx: &mpsc::UnboundedSender<ProgressUpdate>,
+    start_time: Instant,
+    send_engine_progress_update: &impl Fn(Engine, EngineProgressUpdate),
+) -> eyre::Result<()> {
+    let mut requests = Vec::new();
+    for &engine in engines {
+        let engine_config = query.config.engines.get(engine);
+        if !engine_config.enabled {
+            continue;
+        }
+        if circuit_breaker::should_skip(engine, &query.config.circuit_breaker) {
+            info!("Skipping {engine} since its circuit breaker is open");
+            continue;
+        }
 
+        requests.push(async move {
+            let response = request_engine(engine, query, send_engine_progress_update).await?;
             Ok((engine, response))
         });
     }
@@ -427,6 +534,8 @@ async fn make_requests(
     for response_result in join_all(response_futures).await {
         let response_result: eyre::Result<_> = response_result; // this line is necessary to make type inference work
         if let Ok((engine, response)) = response_result {
+            metrics::record_engine_results(engine, response.search_results.len());
+            health::record_results(engine, response.search_results.len());
             responses.insert(engine, response);
         }
     }
@@ -442,36 +551,45 @@ async fn make_requests(
         // post-search
 
         let mut postsearch_requests = Vec::new();
-        for &engine in Engine::all() {
+        for &engine in engines {
             let engine_config = query.config.engines.get(engine);
             if !engine_config.enabled {
                 continue;
             }
 
             if let Some(request) = engine.postsearch_request(&response).await {
-                postsearch_requests.push(async move {
-                    let response = match request.send().await {
-                        Ok(mut res) => {
-                            let mut body_bytes = Vec::new();
-                            while let Some(chunk) = res.chunk().await? {
-                                body_bytes.extend_from_slice(&chunk);
+                postsearch_requests.push(
+                    async move {
+                        let response = match request.send().await {
+                            Ok(mut res) => {
+                                let mut body_bytes = Vec::new();
+                                while let Some(chunk) = res.chunk().await? {
+                                    body_bytes.extend_from_slice(&chunk);
+                                }
+                                let body = String::from_utf8_lossy(&body_bytes).to_string();
+
+                                let http_response = HttpResponse {
+                                    res,
+                                    body,
+                                    config: query.config.clone(),
+                                };
+                                run_blocking(move || {
+                                    engine.postsearch_parse_response(&http_response)
+                                })
+                                .await?
                             }
-                            let body = String::from_utf8_lossy(&body_bytes).to_string();
-
-                            let http_response = HttpResponse {
-                                res,
-                                body,
-                                config: query.config.clone(),
-                            };
-                            engine.postsearch_parse_response(&http_response)
-                        }
-                        Err(e) => {
-                            error!("postsearch request error: {e}");
-                            None
-                        }
-                    };
-                    Ok((engine, response))
-                });
+                            Err(e) => {
+                                error!(
+                                    "postsearch request error: {}",
+                                    query.config.logging.loggable_error(&e, &query.query)
+                                );
+                                None
+                            }
+                        };
+                        Ok((engine, response))
+                    }
+                    .instrument(info_span!("postsearch_request", %engine)),
+                );
             }
         }
 
@@ -482,6 +600,7 @@ async fn make_requests(
 
         let postsearch_responses_result: eyre::Result<HashMap<_, _>> =
             join_all(postsearch_response_futures)
+                .instrument(info_span!("postsearch"))
                 .await
                 .into_iter()
                 .collect();
@@ -504,16 +623,21 @@ async fn make_requests(
 
 async fn make_image_requests(
     query: &SearchQuery,
+    engines: &[Engine],
     progress_tx: &mpsc::UnboundedSender<ProgressUpdate>,
     start_time: Instant,
     send_engine_progress_update: &impl Fn(Engine, EngineProgressUpdate),
 ) -> eyre::Result<()> {
     let mut requests = Vec::new();
-    for &engine in Engine::all() {
+    for &engine in engines {
         let engine_config = query.config.engines.get(engine);
         if !engine_config.enabled {
             continue;
         }
+        if circuit_breaker::should_skip(engine, &query.config.circuit_breaker) {
+            info!("Skipping {engine} since its circuit breaker is open");
+            continue;
+        }
 
         requests.push(async move {
             let request_response = engine.request_images(query);
@@ -523,10 +647,17 @@ async fn make_image_requests(
                     let http_response =
                         make_request(*request, engine, query, send_engine_progress_update).await?;
 
-                    let response = match engine.parse_images_response(&http_response) {
+                    let parse_result =
+                        run_blocking(move || engine.parse_images_response(&http_response))
+                            .await
+                            .and_then(|r| r);
+                    let response = match parse_result {
                         Ok(response) => response,
                         Err(e) => {
-                            error!("parse error for {engine} (images): {e}");
+                            error!(
+                                "parse error for {engine} (images): {}",
+                                query.config.logging.loggable_error(&e, &query.query)
+                            );
                             EngineImagesResponse::new()
                         }
                     };
@@ -554,6 +685,10 @@ async fn make_image_requests(
     let responses_result: eyre::Result<HashMap<_, _>> =
         join_all(response_futures).await.into_iter().collect();
     let responses = responses_result?;
+    for (engine, response) in &responses {
+        metrics::record_engine_results(*engine, response.image_results.len());
+        health::record_results(*engine, response.image_results.len());
+    }
 
     let response = ranking::merge_images_responses(query.config.clone(), responses);
     progress_tx.send(ProgressUpdate::new(
@@ -564,7 +699,12 @@ async fn make_image_requests(
     Ok(())
 }
 
-#[tracing::instrument(fields(query = %query.query), skip(progress_tx))]
+#[tracing::instrument(
+    fields(query = %query.config.logging.loggable_query(&query.query)),
+    skip(progress_tx)
+)]
+/// Run a search, sending every update to `progress_tx` as it happens. Most
+/// callers want [`search_with_progress`] or [`search_stream`] instead.
 pub async fn search(
     query: &SearchQuery,
     progress_tx: mpsc::UnboundedSender<ProgressUpdate>,
@@ -573,21 +713,48 @@ pub async fn search(
 
     info!("Doing search");
 
+    // engines can be registered at any time, so every part of the search uses
+    // the engines that were registered when it started
+    let engines = Engine::all();
     let progress_tx = &progress_tx;
+    let previous_updates = Mutex::new(HashMap::new());
     let send_engine_progress_update = |engine: Engine, update: EngineProgressUpdate| {
-        let _ = progress_tx.send(ProgressUpdate::new(
-            ProgressUpdateData::Engine { engine, update },
-            start_time,
-        ));
+        let progress_update =
+            ProgressUpdate::new(ProgressUpdateData::Engine { engine, update }, start_time);
+        if let ProgressUpdateData::Engine { update, .. } = &progress_update.data {
+            let previous = previous_updates.lock().insert(engine, update.clone());
+            metrics::record_engine_progress_update(
+                engine,
+                update,
+                previous.as_ref(),
+                progress_update.time_ms,
+            );
+            health::record_progress_update(engine, update, progress_update.time_ms);
+            circuit_breaker::record_progress_update(engine, update, &query.config.circuit_breaker);
+        }
+        let _ = progress_tx.send(progress_update);
     };
 
     match query.tab {
         SearchTab::All => {
-            make_requests(query, progress_tx, start_time, &send_engine_progress_update).await?
+            make_requests(
+                query,
+                &engines,
+                progress_tx,
+                start_time,
+                &send_engine_progress_update,
+            )
+            .await?
         }
         SearchTab::Images if query.config.image_search.enabled => {
-            make_image_requests(query, progress_tx, start_time, &send_engine_progress_update)
-                .await?
+            make_image_requests(
+                query,
+                &engines,
+                progress_tx,
+                start_time,
+                &send_engine_progress_update,
+            )
+            .await?
         }
         _ => {
             bail!("unknown tab");
@@ -597,41 +764,108 @@ pub async fn search(
     Ok(())
 }
 
+/// The results of a search that finished.
+#[derive(Debug)]
+pub struct FinishedSearch {
+    /// The merged results of every engine.
+    pub response: ResponseForTab,
+    /// Some infoboxes (like from GitHub or Stack Exchange) are found after the
+    /// response is sent, based on its results.
+    pub post_search_infobox: Option<Infobox>,
+}
+
+/// Search and wait for the results, calling `on_progress` with every update
+/// as it happens (including the response itself).
+pub async fn search_with_progress(
+    query: &SearchQuery,
+    mut on_progress: impl FnMut(&ProgressUpdate),
+) -> eyre::Result<FinishedSearch> {
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+
+    let mut response = None;
+    let mut post_search_infobox = None;
+    let receive_updates = async {
+        while let Some(progress_update) = progress_rx.recv().await {
+            on_progress(&progress_update);
+            match progress_update.data {
+                ProgressUpdateData::Response(r) => response = Some(r),
+                ProgressUpdateData::PostSearchInfobox(infobox) => {
+                    post_search_infobox = Some(infobox);
+                }
+                ProgressUpdateData::Engine { .. } => {}
+            }
+        }
+    };
+    let (result, ()) = tokio::join!(search(query, progress_tx), receive_updates);
+    result?;
+
+    Ok(FinishedSearch {
+        response: response.ok_or_else(|| eyre!("search finished without a response"))?,
+        post_search_infobox,
+    })
+}
+
+/// Start a search in the background and stream its progress updates. If the
+/// search fails then the error is the last item.
+pub fn search_stream(query: SearchQuery) -> impl Stream<Item = eyre::Result<ProgressUpdate>> {
+    stream! {
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+        let search_future =
+            tokio::spawn(async move { search(&query, progress_tx).await }.in_current_span());
+
+        while let Some(progress_update) = progress_rx.recv().await {
+            yield Ok(progress_update);
+        }
+        match search_future.await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => yield Err(err),
+            Err(err) => yield Err(err.into()),
+        }
+    }
+}
+
+/// Get suggestions for a partly typed query from every enabled engine that
+/// can autocomplete.
 pub async fn autocomplete(config: &Config, query: &str) -> eyre::Result<Vec<String>> {
     let mut requests = Vec::new();
-    for &engine in Engine::all() {
+    for engine in Engine::all() {
         if !config.ui.show_autocomplete {
             break;
         }
 
-        let config = config.engines.get(engine);
-        if !config.enabled {
+        let autocomplete_kind = engine.autocomplete_kind();
+        if autocomplete_kind == AutocompleteKind::None || !config.engines.get(engine).enabled {
             continue;
         }
 
-        if let Some(request) = engine.request_autocomplete(query) {
-            requests.push(async move {
-                let response = match request {
-                    RequestAutocompleteResponse::Http(request) => {
-                        let res = request.send().await?;
-                        let body = res.text().await?;
-                        engine.parse_autocomplete_response(&body)?
-                    }
-                    RequestAutocompleteResponse::Instant(response) => response,
-                };
-                Ok((engine, response))
-            });
-        }
-    }
-
-    let mut autocomplete_futures = Vec::new();
-    for request in requests {
-        autocomplete_futures.push(request);
+        let owned_query = query.to_string();
+        requests.push(async move {
+            let request = if autocomplete_kind == AutocompleteKind::Instant {
+                // this might be slow, so every engine gets its own blocking thread
+                // and they run at the same time
+                run_blocking(move || engine.request_autocomplete(&owned_query)).await?
+            } else {
+                engine.request_autocomplete(&owned_query)
+            };
+            let response = match request {
+                Some(RequestAutocompleteResponse::Http(request)) => {
+                    let res = request.send().await?;
+                    let body = res.text().await?;
+                    engine.parse_autocomplete_response(&body)?
+                }
+                Some(RequestAutocompleteResponse::Instant(response)) => response,
+                None => return Ok(None),
+            };
+            Ok(Some((engine, response)))
+        });
     }
 
-    let autocomplete_results_result: eyre::Result<HashMap<_, _>> =
-        join_all(autocomplete_futures).await.into_iter().collect();
-    let autocomplete_results = autocomplete_results_result?;
+    let autocomplete_results_result: eyre::Result<Vec<_>> =
+        join_all(requests).await.into_iter().collect();
+    let autocomplete_results = autocomplete_results_result?
+        .into_iter()
+        .flatten()
+        .collect::<HashMap<_, _>>();
 
     Ok(ranking::merge_autocomplete_responses(
         config,
@@ -639,6 +873,7 @@ pub async fn autocomplete(config: &Config, query: &str) -> eyre::Result<Vec<Stri
     ))
 }
 
+/// The client that engines should make their requests with.
 pub static CLIENT: LazyLock<wreq::Client> = LazyLock::new(|| {
     wreq::ClientBuilder::new()
         .local_address(IpAddr::from_str("0.0.0.0").unwrap())
@@ -649,60 +884,87 @@ pub static CLIENT: LazyLock<wreq::Client> = LazyLock::new(|| {
         .unwrap()
 });
 
+/// The merged results for the "All" tab.
 #[derive(Debug, Clone, Serialize)]
 pub struct Response {
+    /// The results, best first.
     pub search_results: Vec<SearchResult<EngineSearchResult>>,
+    /// The featured snippet from the best engine that had one.
     pub featured_snippet: Option<FeaturedSnippet>,
+    /// The answer from the best engine that had one.
     pub answer: Option<Answer>,
+    /// The infobox from the best engine that had one.
     pub infobox: Option<Infobox>,
+    /// The config the search was made with.
     #[serde(skip)]
     pub config: Arc<Config>,
 }
 
+/// The merged results for the "Images" tab.
 #[derive(Debug, Clone, Serialize)]
 pub struct ImagesResponse {
+    /// The results, best first.
     pub image_results: Vec<SearchResult<EngineImageResult>>,
+    /// The config the search was made with.
     #[serde(skip)]
     pub config: Arc<Config>,
 }
 
+/// The merged results for whichever tab was searched.
 #[derive(Debug, Clone, Serialize)]
 #[serde(untagged)]
 pub enum ResponseForTab {
+    /// Results for [`SearchTab::All`].
     All(Response),
+    /// Results for [`SearchTab::Images`].
     Images(ImagesResponse),
 }
 
+/// A result that one or more engines found.
 #[derive(Debug, Clone, Serialize)]
 pub struct SearchResult<R: Serialize> {
+    /// The result, as the best engine that found it had it.
     pub result: R,
+    /// Every engine that found it.
     pub engines: BTreeSet<Engine>,
+    /// How it's ranked, higher is better.
     pub score: f64,
 }
 
+/// An [`EngineFeaturedSnippet`], with the engine it's from.
 #[derive(Debug, Clone, Serialize)]
 pub struct FeaturedSnippet {
+    /// The url of the page.
     pub url: String,
+    /// The title of the page.
     pub title: String,
+    /// A snippet of the page, as plain text.
     pub description: String,
+    /// The engine it's from.
     pub engine: Engine,
 }
 
+/// An answer to the query, like a calculator result.
 #[derive(Debug, Clone, Serialize)]
 pub struct Answer {
+    /// The answer, as html.
     #[serde(serialize_with = "serialize_markup")]
     pub html: PreEscaped<String>,
+    /// The engine it's from.
     pub engine: Engine,
 }
 
+/// Information about the thing being searched for, shown next to the results.
 #[derive(Debug, Clone, Serialize)]
 pub struct Infobox {
+    /// The infobox, as html.
     #[serde(serialize_with = "serialize_markup")]
     pub html: PreEscaped<String>,
+    /// The engine it's from.
     pub engine: Engine,
 }
 
-pub struct AutocompleteResult {
+pub(crate) struct AutocompleteResult {
     pub query: String,
     pub score: f64,
 }
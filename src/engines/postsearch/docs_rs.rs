@@ -1,7 +1,24 @@
 use maud::{html, PreEscaped};
 use scraper::{Html, Selector};
 
-use crate::engines::{HttpResponse, Response, CLIENT};
+use crate::engines::{async_trait, Engine, EngineImpl, HttpResponse, Response, CLIENT};
+
+pub struct DocsRs;
+
+#[async_trait]
+impl EngineImpl for DocsRs {
+    fn id(&self) -> &'static str {
+        Engine::DocsRs.id()
+    }
+
+    async fn postsearch_request(&self, response: &Response) -> Option<wreq::RequestBuilder> {
+        request(response).await
+    }
+
+    fn postsearch_parse_response(&self, res: &HttpResponse) -> Option<PreEscaped<String>> {
+        parse_response(res)
+    }
+}
 
 pub async fn request(response: &Response) -> Option<wreq::RequestBuilder> {
     for search_result in response.search_results.iter().take(8) {
@@ -2,7 +2,26 @@ use maud::{html, PreEscaped};
 use scraper::{Html, Selector};
 use url::Url;
 
-use crate::engines::{answer::regex, Response, CLIENT};
+use crate::engines::{
+    answer::regex, async_trait, Engine, EngineImpl, HttpResponse, Response, CLIENT,
+};
+
+pub struct GitHub;
+
+#[async_trait]
+impl EngineImpl for GitHub {
+    fn id(&self) -> &'static str {
+        Engine::GitHub.id()
+    }
+
+    async fn postsearch_request(&self, response: &Response) -> Option<wreq::RequestBuilder> {
+        request(response).await
+    }
+
+    fn postsearch_parse_response(&self, res: &HttpResponse) -> Option<PreEscaped<String>> {
+        parse_response(res.into())
+    }
+}
 
 pub async fn request(response: &Response) -> Option<wreq::RequestBuilder> {
     for search_result in response.search_results.iter().take(8) {
@@ -1,15 +1,38 @@
 use maud::{html, PreEscaped};
 use scraper::{Html, Selector};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tracing::error;
 
-use crate::engines::{Engine, HttpResponse, Response, CLIENT};
+use crate::engines::{
+    async_trait, options_table, Engine, EngineImpl, HttpResponse, Response, CLIENT,
+};
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct MdnConfig {
     pub max_sections: usize,
 }
 
+pub struct Mdn;
+
+#[async_trait]
+impl EngineImpl for Mdn {
+    fn id(&self) -> &'static str {
+        Engine::Mdn.id()
+    }
+
+    fn default_options(&self) -> toml::Table {
+        options_table(&MdnConfig { max_sections: 1 })
+    }
+
+    async fn postsearch_request(&self, response: &Response) -> Option<wreq::RequestBuilder> {
+        request(response).await
+    }
+
+    fn postsearch_parse_response(&self, res: &HttpResponse) -> Option<PreEscaped<String>> {
+        parse_response(res)
+    }
+}
+
 pub async fn request(response: &Response) -> Option<wreq::RequestBuilder> {
     for search_result in response.search_results.iter().take(8) {
         if search_result
@@ -27,8 +50,7 @@ pub async fn request(response: &Response) -> Option<wreq::RequestBuilder> {
 pub fn parse_response(
     HttpResponse { res, body, config }: &HttpResponse,
 ) -> Option<PreEscaped<String>> {
-    let config_toml = config.engines.get(Engine::Mdn).extra.clone();
-    let config: MdnConfig = match toml::Value::Table(config_toml).try_into() {
+    let config: MdnConfig = match config.engines.get(Engine::Mdn).options() {
         Ok(args) => args,
         Err(err) => {
             error!("Failed to parse Mdn config: {err}");
@@ -10,6 +10,7 @@ use super::{
     EngineSearchResult, FeaturedSnippet, ImagesResponse, Infobox, Response, SearchResult,
 };
 
+#[tracing::instrument(skip_all)]
 pub fn merge_engine_responses(
     config: Arc<Config>,
     responses: HashMap<Engine, EngineResponse>,
@@ -168,6 +169,7 @@ pub fn merge_autocomplete_responses(
     autocomplete_results.into_iter().map(|r| r.query).collect()
 }
 
+#[tracing::instrument(skip_all)]
 pub fn merge_images_responses(
     config: Arc<Config>,
     responses: HashMap<Engine, EngineImagesResponse>,
@@ -0,0 +1,409 @@
+//! The engines that can be used in searches. The built-in engines are always
+//! registered, and other crates can add their own with [`register`].
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{Arc, LazyLock},
+};
+
+use async_trait::async_trait;
+use maud::PreEscaped;
+use parking_lot::RwLock;
+use serde::Serialize;
+
+use super::{
+    builtin_engines, AutocompleteKind, EngineImagesResponse, EngineResponse, HttpResponse,
+    RequestAutocompleteResponse, RequestResponse, Response, SearchQuery,
+};
+use crate::config::EngineConfig;
+
+/// An engine and the things it can do. Every capability is optional, so an
+/// engine only has to implement the methods it needs.
+///
+/// Answer engines (like calculators) use [`EngineImpl::request`] too, but
+/// they usually return an instant response with `answer_html` or
+/// `infobox_html` set instead of making an HTTP request.
+#[async_trait]
+pub trait EngineImpl: Send + Sync + 'static {
+    /// The id that's used in the config and shown in the results, like
+    /// `google`.
+    fn id(&self) -> &'static str;
+
+    /// Whether this is a general web search engine, as opposed to an answer or
+    /// post-search engine.
+    fn is_search_engine(&self) -> bool {
+        false
+    }
+
+    /// The priority of this engine relative to the other engines, if it's not
+    /// set in the config.
+    fn default_weight(&self) -> f64 {
+        1.0
+    }
+
+    /// Whether the engine is used if the config doesn't say.
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    /// The options that are used if they're not set in the engine's section of
+    /// the config. See [`options_table`] and [`EngineConfig::options`].
+    fn default_options(&self) -> toml::Table {
+        toml::Table::new()
+    }
+
+    /// Make the request for a search on the "All" tab, or respond to it
+    /// directly.
+    async fn request(&self, _query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        Ok(RequestResponse::None)
+    }
+
+    /// Parse the response to the request from [`EngineImpl::request`].
+    fn parse_response(&self, _res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        eyre::bail!("engine {} can't parse response", self.id())
+    }
+
+    /// Make the request for a search on the "Images" tab.
+    fn request_images(&self, _query: &SearchQuery) -> RequestResponse {
+        RequestResponse::None
+    }
+
+    /// Parse the response to the request from [`EngineImpl::request_images`].
+    fn parse_images_response(&self, _res: &HttpResponse) -> eyre::Result<EngineImagesResponse> {
+        eyre::bail!("engine {} can't parse images response", self.id())
+    }
+
+    /// Engines that autocomplete should return something other than
+    /// [`AutocompleteKind::None`] and implement
+    /// [`EngineImpl::request_autocomplete`].
+    fn autocomplete_kind(&self) -> AutocompleteKind {
+        AutocompleteKind::None
+    }
+
+    /// Make the request for autocomplete suggestions, or return them
+    /// directly.
+    fn request_autocomplete(&self, _query: &str) -> Option<RequestAutocompleteResponse> {
+        None
+    }
+
+    /// Parse the response to the request from
+    /// [`EngineImpl::request_autocomplete`].
+    fn parse_autocomplete_response(&self, _body: &str) -> eyre::Result<Vec<String>> {
+        eyre::bail!("engine {} can't parse autocomplete response", self.id())
+    }
+
+    /// Make a request based on the results of the search, for engines that
+    /// show an infobox about one of the results.
+    async fn postsearch_request(&self, _response: &Response) -> Option<wreq::RequestBuilder> {
+        None
+    }
+
+    /// Parse the response to the request from
+    /// [`EngineImpl::postsearch_request`] into an infobox.
+    fn postsearch_parse_response(&self, _res: &HttpResponse) -> Option<PreEscaped<String>> {
+        None
+    }
+}
+
+static REGISTRY: LazyLock<RwLock<Vec<Arc<dyn EngineImpl>>>> =
+    LazyLock::new(|| RwLock::new(builtin_engines()));
+
+/// Add an engine, or replace the engine that has the same id. Engines should
+/// be registered before the config is read, since that's where their defaults
+/// come from.
+pub fn register(engine: impl EngineImpl) -> Engine {
+    let id = engine.id();
+    let engine: Arc<dyn EngineImpl> = Arc::new(engine);
+
+    let mut registry = REGISTRY.write();
+    if let Some(existing) = registry.iter_mut().find(|e| e.id() == id) {
+        *existing = engine;
+    } else {
+        registry.push(engine);
+    }
+    Engine(id)
+}
+
+/// Serialize an engine's options into a table, for
+/// [`EngineImpl::default_options`].
+pub fn options_table(options: &impl Serialize) -> toml::Table {
+    toml::Table::try_from(options).expect("engine options must serialize to a table")
+}
+
+/// A registered engine. This is cheap to copy and compares by id.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct Engine(&'static str);
+
+impl Engine {
+    /// Every registered engine, in the order that they were registered.
+    #[must_use]
+    pub fn all() -> Vec<Engine> {
+        REGISTRY.read().iter().map(|e| Engine(e.id())).collect()
+    }
+
+    /// The id that's used in the config, like `google`.
+    #[must_use]
+    pub const fn id(&self) -> &'static str {
+        self.0
+    }
+
+    fn implementation(self) -> Option<Arc<dyn EngineImpl>> {
+        REGISTRY.read().iter().find(|e| e.id() == self.0).cloned()
+    }
+
+    fn registered_implementation(self) -> eyre::Result<Arc<dyn EngineImpl>> {
+        self.implementation()
+            .ok_or_else(|| eyre::eyre!("engine {self} isn't registered"))
+    }
+
+    /// See [`EngineImpl::is_search_engine`].
+    #[must_use]
+    pub fn is_search_engine(&self) -> bool {
+        self.implementation()
+            .is_some_and(|engine| engine.is_search_engine())
+    }
+
+    /// The config that's used for this engine if it's not overridden. Engines
+    /// that aren't registered are disabled.
+    #[must_use]
+    pub fn default_config(&self) -> EngineConfig {
+        let Some(engine) = self.implementation() else {
+            return EngineConfig::new().disabled();
+        };
+        let config = EngineConfig::new()
+            .with_weight(engine.default_weight())
+            .with_extra(engine.default_options());
+        if engine.enabled_by_default() {
+            config
+        } else {
+            config.disabled()
+        }
+    }
+
+    /// See [`EngineImpl::request`].
+    pub async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        self.registered_implementation()?.request(query).await
+    }
+
+    /// See [`EngineImpl::parse_response`].
+    #[tracing::instrument(skip(self, res), fields(engine = %self))]
+    pub fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        self.registered_implementation()?.parse_response(res)
+    }
+
+    /// See [`EngineImpl::request_images`].
+    #[must_use]
+    pub fn request_images(&self, query: &SearchQuery) -> RequestResponse {
+        self.implementation()
+            .map_or(RequestResponse::None, |engine| engine.request_images(query))
+    }
+
+    /// See [`EngineImpl::parse_images_response`].
+    pub fn parse_images_response(&self, res: &HttpResponse) -> eyre::Result<EngineImagesResponse> {
+        self.registered_implementation()?.parse_images_response(res)
+    }
+
+    /// See [`EngineImpl::autocomplete_kind`].
+    #[must_use]
+    pub fn autocomplete_kind(&self) -> AutocompleteKind {
+        self.implementation()
+            .map_or(AutocompleteKind::None, |engine| engine.autocomplete_kind())
+    }
+
+    /// See [`EngineImpl::request_autocomplete`].
+    #[must_use]
+    pub fn request_autocomplete(&self, query: &str) -> Option<RequestAutocompleteResponse> {
+        self.implementation()?.request_autocomplete(query)
+    }
+
+    /// See [`EngineImpl::parse_autocomplete_response`].
+    pub fn parse_autocomplete_response(&self, body: &str) -> eyre::Result<Vec<String>> {
+        self.registered_implementation()?
+            .parse_autocomplete_response(body)
+    }
+
+    /// See [`EngineImpl::postsearch_request`].
+    pub async fn postsearch_request(&self, response: &Response) -> Option<wreq::RequestBuilder> {
+        self.implementation()?.postsearch_request(response).await
+    }
+
+    /// See [`EngineImpl::postsearch_parse_response`].
+    #[must_use]
+    pub fn postsearch_parse_response(&self, res: &HttpResponse) -> Option<PreEscaped<String>> {
+        self.implementation()?.postsearch_parse_response(res)
+    }
+}
+
+impl FromStr for Engine {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        REGISTRY
+            .read()
+            .iter()
+            .find(|e| e.id() == s)
+            .map(|e| Engine(e.id()))
+            .ok_or(())
+    }
+}
+
+impl fmt::Debug for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The ids of the built-in engines, so they can be referred to without
+/// looking them up.
+#[allow(non_upper_case_globals, missing_docs)]
+impl Engine {
+    // search
+    pub const Google: Engine = Engine("google");
+    pub const GoogleScholar: Engine = Engine("google_scholar");
+    pub const Bing: Engine = Engine("bing");
+    pub const Brave: Engine = Engine("brave");
+    pub const Marginalia: Engine = Engine("marginalia");
+    pub const RightDao: Engine = Engine("rightdao");
+    pub const Stract: Engine = Engine("stract");
+    pub const Yep: Engine = Engine("yep");
+    // answer
+    pub const Dictionary: Engine = Engine("dictionary");
+    pub const Fend: Engine = Engine("fend");
+    pub const Ip: Engine = Engine("ip");
+    pub const Notepad: Engine = Engine("notepad");
+    pub const ColorPicker: Engine = Engine("colorpicker");
+    pub const Numbat: Engine = Engine("numbat");
+    pub const Thesaurus: Engine = Engine("thesaurus");
+    pub const Timezone: Engine = Engine("timezone");
+    pub const Useragent: Engine = Engine("useragent");
+    pub const Wikipedia: Engine = Engine("wikipedia");
+    // post-search
+    pub const DocsRs: Engine = Engine("docs_rs");
+    pub const GitHub: Engine = Engine("github");
+    pub const Mdn: Engine = Engine("mdn");
+    pub const MinecraftWiki: Engine = Engine("minecraft_wiki");
+    pub const StackExchange: Engine = Engine("stackexchange");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct TestOptions {
+        count: u32,
+        region: String,
+    }
+
+    struct TestEngine {
+        id: &'static str,
+        weight: f64,
+        enabled: bool,
+    }
+
+    impl EngineImpl for TestEngine {
+        fn id(&self) -> &'static str {
+            self.id
+        }
+
+        fn default_weight(&self) -> f64 {
+            self.weight
+        }
+
+        fn enabled_by_default(&self) -> bool {
+            self.enabled
+        }
+
+        fn default_options(&self) -> toml::Table {
+            options_table(&TestOptions {
+                count: 10,
+                region: "us".to_string(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_register_replaces_engine_with_same_id() {
+        let engine = register(TestEngine {
+            id: "test_replaced",
+            weight: 2.,
+            enabled: true,
+        });
+        assert_eq!(engine.default_config().weight, 2.);
+
+        let replacement = register(TestEngine {
+            id: "test_replaced",
+            weight: 3.,
+            enabled: true,
+        });
+        assert_eq!(replacement, engine);
+        assert_eq!(engine.default_config().weight, 3.);
+        assert_eq!(Engine::all().iter().filter(|e| **e == engine).count(), 1);
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(Engine::from_str("google"), Ok(Engine::Google));
+        assert_eq!(Engine::from_str("test_unregistered"), Err(()));
+
+        let engine = register(TestEngine {
+            id: "test_from_str",
+            weight: 1.,
+            enabled: true,
+        });
+        assert_eq!(Engine::from_str("test_from_str"), Ok(engine));
+    }
+
+    #[test]
+    fn test_default_config() {
+        let engine = register(TestEngine {
+            id: "test_default_config",
+            weight: 0.5,
+            enabled: false,
+        });
+        let config = engine.default_config();
+        assert_eq!(config.weight, 0.5);
+        assert!(!config.enabled);
+        assert_eq!(
+            config.options::<TestOptions>().unwrap(),
+            TestOptions {
+                count: 10,
+                region: "us".to_string(),
+            }
+        );
+
+        // options from the config are merged with the defaults
+        let mut config = engine.default_config();
+        config.overlay(toml::from_str("enabled = true\ncount = 20").unwrap());
+        assert!(config.enabled);
+        assert_eq!(
+            config.options::<TestOptions>().unwrap(),
+            TestOptions {
+                count: 20,
+                region: "us".to_string(),
+            }
+        );
+        assert!(config.options::<HashMap<String, u32>>().is_err());
+    }
+
+    #[test]
+    fn test_unregistered_engine() {
+        let engine = Engine("test_unregistered");
+        assert!(!engine.is_search_engine());
+        assert!(!engine.default_config().enabled);
+        assert_eq!(engine.autocomplete_kind(), AutocompleteKind::None);
+        assert!(engine.request_autocomplete("rust").is_none());
+        assert!(engine.parse_autocomplete_response("[]").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use base64::Engine;
+use base64::Engine as _;
 use eyre::eyre;
 use rand::Rng;
 use scraper::{ElementRef, Html, Selector};
@@ -6,10 +6,42 @@ use tracing::warn;
 use url::Url;
 
 use crate::{
-    engines::{EngineImageResult, EngineImagesResponse, EngineResponse, CLIENT},
+    engines::{
+        async_trait, Engine, EngineImageResult, EngineImagesResponse, EngineImpl, EngineResponse,
+        HttpResponse, IntoRequestResponseResult, RequestResponse, SearchQuery, CLIENT,
+    },
     parse::{parse_html_response_with_opts, ParseOpts, QueryMethod},
 };
 
+pub struct Bing;
+
+#[async_trait]
+impl EngineImpl for Bing {
+    fn id(&self) -> &'static str {
+        Engine::Bing.id()
+    }
+
+    fn is_search_engine(&self) -> bool {
+        true
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+
+    fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        parse_response(res.into())
+    }
+
+    fn request_images(&self, query: &SearchQuery) -> RequestResponse {
+        request_images(query).into()
+    }
+
+    fn parse_images_response(&self, res: &HttpResponse) -> eyre::Result<EngineImagesResponse> {
+        parse_images_response(res.into())
+    }
+}
+
 pub async fn request(query: &str) -> wreq::RequestBuilder {
     let cvid = generate_cvid();
     let url = Url::parse_with_params(
@@ -5,12 +5,58 @@ use url::Url;
 
 use crate::{
     engines::{
-        EngineImageResult, EngineImagesResponse, EngineResponse, RequestResponse, SearchQuery,
-        CLIENT,
+        async_trait, AutocompleteKind, Engine, EngineImageResult, EngineImagesResponse, EngineImpl,
+        EngineResponse, HttpResponse, IntoRequestResponseResult, RequestAutocompleteResponse,
+        RequestResponse, SearchQuery, CLIENT,
     },
     parse::{parse_html_response_with_opts, ParseOpts, QueryMethod},
 };
 
+pub struct Google;
+
+#[async_trait]
+impl EngineImpl for Google {
+    fn id(&self) -> &'static str {
+        Engine::Google.id()
+    }
+
+    fn is_search_engine(&self) -> bool {
+        true
+    }
+
+    fn default_weight(&self) -> f64 {
+        1.05
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+
+    fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        parse_response(res.into())
+    }
+
+    fn autocomplete_kind(&self) -> AutocompleteKind {
+        AutocompleteKind::Http
+    }
+
+    fn request_autocomplete(&self, query: &str) -> Option<RequestAutocompleteResponse> {
+        Some(request_autocomplete(query).into())
+    }
+
+    fn parse_autocomplete_response(&self, body: &str) -> eyre::Result<Vec<String>> {
+        parse_autocomplete_response(body)
+    }
+
+    fn request_images(&self, query: &SearchQuery) -> RequestResponse {
+        request_images(query).into()
+    }
+
+    fn parse_images_response(&self, res: &HttpResponse) -> eyre::Result<EngineImagesResponse> {
+        parse_images_response(res.into())
+    }
+}
+
 pub async fn request(search: &SearchQuery) -> eyre::Result<RequestResponse> {
     let url = Url::parse_with_params(
         "https://www.google.com/search",
@@ -1,10 +1,42 @@
 use url::Url;
 
 use crate::{
-    engines::{EngineResponse, RequestResponse, CLIENT},
+    engines::{
+        async_trait, Engine, EngineImpl, EngineResponse, HttpResponse, IntoRequestResponseResult,
+        RequestResponse, SearchQuery, CLIENT,
+    },
     parse::{parse_html_response_with_opts, ParseOpts},
 };
 
+pub struct GoogleScholar;
+
+#[async_trait]
+impl EngineImpl for GoogleScholar {
+    fn id(&self) -> &'static str {
+        Engine::GoogleScholar.id()
+    }
+
+    fn is_search_engine(&self) -> bool {
+        true
+    }
+
+    fn default_weight(&self) -> f64 {
+        0.50
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+
+    fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        parse_response(res.into())
+    }
+}
+
 pub async fn request(query: &str) -> RequestResponse {
     CLIENT
         .get(
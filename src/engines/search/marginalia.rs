@@ -1,23 +1,61 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tracing::error;
 use url::Url;
 
 use crate::{
-    engines::{Engine, EngineResponse, RequestResponse, SearchQuery, CLIENT},
+    engines::{
+        async_trait, options_table, Engine, EngineImpl, EngineResponse, HttpResponse,
+        IntoRequestResponseResult, RequestResponse, SearchQuery, CLIENT,
+    },
     parse::{parse_html_response_with_opts, ParseOpts},
 };
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct MarginaliaConfig {
     pub args: MarginaliaArgs,
 }
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct MarginaliaArgs {
     pub profile: String,
     pub js: String,
     pub adtech: String,
 }
 
+pub struct Marginalia;
+
+#[async_trait]
+impl EngineImpl for Marginalia {
+    fn id(&self) -> &'static str {
+        Engine::Marginalia.id()
+    }
+
+    fn is_search_engine(&self) -> bool {
+        true
+    }
+
+    fn default_weight(&self) -> f64 {
+        0.15
+    }
+
+    fn default_options(&self) -> toml::Table {
+        options_table(&MarginaliaConfig {
+            args: MarginaliaArgs {
+                profile: "corpo".to_string(),
+                js: "default".to_string(),
+                adtech: "default".to_string(),
+            },
+        })
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+
+    fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        parse_response(res.into())
+    }
+}
+
 pub async fn request(query: &SearchQuery) -> RequestResponse {
     // if the query is more than 3 words or has any special characters then abort
     if query.split_whitespace().count() > 3
@@ -26,8 +64,7 @@ pub async fn request(query: &SearchQuery) -> RequestResponse {
         return RequestResponse::None;
     }
 
-    let config_toml = query.config.engines.get(Engine::Marginalia).extra.clone();
-    let config: MarginaliaConfig = match toml::Value::Table(config_toml).try_into() {
+    let config: MarginaliaConfig = match query.config.engines.get(Engine::Marginalia).options() {
         Ok(args) => args,
         Err(err) => {
             error!("Failed to parse Marginalia config: {err}");
@@ -1,10 +1,42 @@
 use url::Url;
 
 use crate::{
-    engines::{EngineResponse, RequestResponse, CLIENT},
+    engines::{
+        async_trait, Engine, EngineImpl, EngineResponse, HttpResponse, IntoRequestResponseResult,
+        RequestResponse, SearchQuery, CLIENT,
+    },
     parse::{parse_html_response_with_opts, ParseOpts},
 };
 
+pub struct RightDao;
+
+#[async_trait]
+impl EngineImpl for RightDao {
+    fn id(&self) -> &'static str {
+        Engine::RightDao.id()
+    }
+
+    fn is_search_engine(&self) -> bool {
+        true
+    }
+
+    fn default_weight(&self) -> f64 {
+        0.10
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+
+    fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        parse_response(res.into())
+    }
+}
+
 pub async fn request(query: &str) -> RequestResponse {
     CLIENT
         .get(Url::parse_with_params("https://rightdao.com/search", &[("q", query)]).unwrap())
@@ -1,7 +1,39 @@
 use serde::Deserialize;
 use url::Url;
 
-use crate::engines::{EngineResponse, EngineSearchResult, RequestResponse, CLIENT};
+use crate::engines::{
+    async_trait, Engine, EngineImpl, EngineResponse, EngineSearchResult, HttpResponse,
+    IntoRequestResponseResult, RequestResponse, SearchQuery, CLIENT,
+};
+
+pub struct Yep;
+
+#[async_trait]
+impl EngineImpl for Yep {
+    fn id(&self) -> &'static str {
+        Engine::Yep.id()
+    }
+
+    fn is_search_engine(&self) -> bool {
+        true
+    }
+
+    fn default_weight(&self) -> f64 {
+        0.10
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+        request(query).await.into_request_response_result()
+    }
+
+    fn parse_response(&self, res: &HttpResponse) -> eyre::Result<EngineResponse> {
+        parse_response(res.into())
+    }
+}
 
 pub async fn request(query: &str) -> RequestResponse {
     CLIENT
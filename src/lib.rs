@@ -0,0 +1,80 @@
+//! A cute metasearch engine.
+//!
+//! Besides running the web server, metasearch can be used as a library to
+//! search from your own programs:
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use metasearch::{Config, ResponseForTab, SearchQuery};
+//!
+//! # async fn example() -> eyre::Result<()> {
+//! // or Config::read(path) to use a config file, after
+//! // engines::custom::register_from_file(path) if it has custom engines
+//! let config = Arc::new(Config::default());
+//! let query = SearchQuery::new("sandcats", config);
+//!
+//! let search = metasearch::search_with_progress(&query, |update| {
+//!     println!("{}ms: {:?}", update.time_ms, update.data);
+//! })
+//! .await?;
+//! if let ResponseForTab::All(response) = search.response {
+//!     for result in response.search_results {
+//!         println!("{} - {}", result.result.title, result.result.url);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`search_stream`] does the same thing, but gives you a stream of the
+//! updates instead. Searches have to run inside of a Tokio runtime.
+//!
+//! You can also add your own engines by implementing [`EngineImpl`] and
+//! registering it before the config is read:
+//!
+//! ```
+//! use metasearch::engines::{
+//!     async_trait, EngineImpl, EngineResponse, RequestResponse, SearchQuery,
+//! };
+//! use maud::html;
+//!
+//! struct Shout;
+//!
+//! #[async_trait]
+//! impl EngineImpl for Shout {
+//!     fn id(&self) -> &'static str {
+//!         "shout"
+//!     }
+//!
+//!     async fn request(&self, query: &SearchQuery) -> eyre::Result<RequestResponse> {
+//!         let answer = html! { h3 { (query.to_uppercase()) } };
+//!         Ok(RequestResponse::Instant(Box::new(EngineResponse::answer_html(answer))))
+//!     }
+//! }
+//!
+//! let engine = metasearch::engines::register(Shout);
+//! assert_eq!(engine.id(), "shout");
+//! assert!(metasearch::Config::default().engines.get(engine).enabled);
+//! ```
+
+#![warn(missing_docs)]
+
+#[doc(hidden)]
+pub mod cli;
+pub mod config;
+pub mod engines;
+#[doc(hidden)]
+pub mod logging;
+mod metrics;
+pub mod parse;
+pub mod response;
+pub mod urls;
+#[doc(hidden)]
+pub mod web;
+
+pub use config::Config;
+pub use engines::{
+    search_stream, search_with_progress, Engine, EngineImpl, EngineProgressUpdate, FinishedSearch,
+    ProgressUpdate, ProgressUpdateData, ResponseForTab, SearchQuery, SearchTab,
+};
@@ -0,0 +1,84 @@
+//! Setting up where logs and traces go.
+
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+use crate::config::LoggingConfig;
+
+#[cfg(feature = "otel")]
+static TRACER_PROVIDER: std::sync::OnceLock<opentelemetry_sdk::trace::SdkTracerProvider> =
+    std::sync::OnceLock::new();
+
+pub fn init(config: &LoggingConfig) {
+    let fmt_layer = if config.json {
+        tracing_subscriber::fmt::layer().json().boxed()
+    } else {
+        tracing_subscriber::fmt::layer().boxed()
+    };
+
+    let registry = tracing_subscriber::registry().with(fmt_layer);
+
+    // the error is logged once the subscriber is ready, so it's formatted like the
+    // rest of the logs
+    #[cfg(feature = "otel")]
+    let (otel_layer, otel_error) = match config.otlp_endpoint.as_deref().map(otel_layer) {
+        Some(Ok(layer)) => (Some(layer), None),
+        Some(Err(err)) => (None, Some(err)),
+        None => (None, None),
+    };
+    #[cfg(feature = "otel")]
+    let registry = registry.with(otel_layer);
+
+    registry.init();
+
+    #[cfg(feature = "otel")]
+    if let Some(err) = otel_error {
+        tracing::error!("{err}");
+    }
+
+    #[cfg(not(feature = "otel"))]
+    if config.otlp_endpoint.is_some() {
+        tracing::warn!(
+            "logging.otlp_endpoint is set, but metasearch wasn't built with the `otel` feature"
+        );
+    }
+}
+
+/// Send any traces that haven't been exported yet. This should be called before
+/// exiting.
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    if let Some(provider) = TRACER_PROVIDER.get() {
+        if let Err(err) = provider.shutdown() {
+            tracing::error!("Couldn't shut down the OpenTelemetry exporter: {err}");
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+fn otel_layer<S>(endpoint: &str) -> eyre::Result<impl Layer<S>>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|err| {
+            eyre::eyre!("Couldn't create OpenTelemetry exporter for {endpoint}: {err}")
+        })?;
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name(env!("CARGO_PKG_NAME"))
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    let _ = TRACER_PROVIDER.set(provider);
+
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
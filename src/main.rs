@@ -1,40 +1,111 @@
 use std::{
     env,
     path::{Path, PathBuf},
+    process::ExitCode,
 };
 
-use config::Config;
-use tracing::error;
-
-pub mod config;
-pub mod engines;
-pub mod parse;
-pub mod urls;
-pub mod web;
+use metasearch::{
+    cli,
+    config::{Config, RuntimeConfig},
+    engines::custom,
+    logging, web,
+};
+use tracing::{error, Level};
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() {
-    tracing_subscriber::fmt::init();
+fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    // only the first argument is checked, so searches like `search vitamin h` work
+    if matches!(
+        args.first().map(String::as_str),
+        Some("--help" | "-h" | "help" | "h")
+    ) {
+        println!("{}", cli::USAGE);
+        return ExitCode::SUCCESS;
+    }
 
-    if env::args().any(|arg| arg == "--help" || arg == "-h" || arg == "help" || arg == "h") {
-        println!("Usage: metasearch [config_path]");
-        return;
+    let cli_args = match cli::parse_args(&args) {
+        Ok(cli_args) => cli_args,
+        Err(err) => {
+            eprintln!("{err}\n\n{}", cli::USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(cli_args) = cli_args {
+        return run_cli(cli_args);
     }
 
-    let config_path = config_path();
-    let config = match Config::read_or_create(&config_path) {
+    let config_path = config_path(args.first().map(PathBuf::from));
+    // we don't know how to format logs until the config is read, so anything
+    // logged before that is plain text
+    let config = match tracing::subscriber::with_default(tracing_subscriber::fmt().finish(), || {
+        custom::register_from_file(&config_path)?;
+        Config::read_or_create(&config_path)
+    }) {
         Ok(config) => config,
         Err(err) => {
+            tracing_subscriber::fmt::init();
             error!("Couldn't parse config:\n{err}");
-            return;
+            return ExitCode::FAILURE;
+        }
+    };
+    logging::init(&config.logging);
+
+    let runtime = match build_runtime(&config.runtime) {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            error!("Couldn't start the async runtime: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    runtime.block_on(web::run(config));
+    logging::shutdown();
+    ExitCode::SUCCESS
+}
+
+fn run_cli(cli_args: cli::CliArgs) -> ExitCode {
+    // stdout is for the results, so only warnings and errors are logged (to
+    // stderr)
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(Level::WARN)
+        .init();
+
+    // unlike the web server, we don't create a config if there isn't one
+    let config_path = config_path(cli_args.config_path.clone());
+    let config =
+        match custom::register_from_file(&config_path).and_then(|()| Config::read(&config_path)) {
+            Ok(config) => config,
+            Err(err) => {
+                error!("Couldn't parse config:\n{err}");
+                return ExitCode::FAILURE;
+            }
+        };
+    let runtime = match build_runtime(&config.runtime) {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            error!("Couldn't start the async runtime: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    runtime.block_on(cli::run(cli_args, config))
+}
+
+fn build_runtime(config: &RuntimeConfig) -> std::io::Result<tokio::runtime::Runtime> {
+    let mut builder = if config.multi_threaded {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        if let Some(worker_threads) = config.worker_threads {
+            builder.worker_threads(worker_threads);
         }
+        builder
+    } else {
+        tokio::runtime::Builder::new_current_thread()
     };
-    web::run(config).await;
+    builder.enable_all().build()
 }
 
-fn config_path() -> PathBuf {
-    if let Some(config_path) = env::args().nth(1) {
-        return PathBuf::from(config_path);
+fn config_path(given_path: Option<PathBuf>) -> PathBuf {
+    if let Some(config_path) = given_path {
+        return config_path;
     }
 
     let app_name = env!("CARGO_PKG_NAME");
@@ -0,0 +1,297 @@
+//! Metrics about engines and HTTP requests, exposed in the Prometheus text
+//! format at `/metrics`.
+
+use std::{collections::BTreeMap, fmt::Write, sync::LazyLock, time::Duration};
+
+use parking_lot::Mutex;
+
+use crate::engines::{Engine, EngineProgressUpdate};
+
+static METRICS: LazyLock<Mutex<Metrics>> = LazyLock::new(|| Mutex::new(Metrics::default()));
+
+/// The upper bounds of the buckets used for latency histograms, in seconds.
+const LATENCY_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1., 2.5, 5., 10.];
+
+#[derive(Default)]
+struct Metrics {
+    engine_requests: BTreeMap<Engine, u64>,
+    engine_errors: BTreeMap<(Engine, EngineErrorKind), u64>,
+    engine_latency: BTreeMap<Engine, Histogram>,
+    engine_results: BTreeMap<Engine, u64>,
+    engine_degraded: BTreeMap<Engine, bool>,
+    image_cache_hits: u64,
+    image_cache_misses: u64,
+    http_requests: BTreeMap<(String, String, u16), u64>,
+    http_latency: BTreeMap<(String, String), Histogram>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EngineErrorKind {
+    /// Building or sending the request failed.
+    Request,
+    /// The response couldn't be downloaded.
+    Download,
+    /// The response couldn't be parsed.
+    Parse,
+}
+
+impl EngineErrorKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            EngineErrorKind::Request => "request",
+            EngineErrorKind::Download => "download",
+            EngineErrorKind::Parse => "parse",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// The number of observations in each of the `LATENCY_BUCKETS`. These
+    /// aren't cumulative, that's done when rendering.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|&bound| value <= bound) {
+            self.buckets[i] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {}", self.count);
+    }
+}
+
+/// Record a progress update from an engine. `previous` is the last update that
+/// the engine sent in this search, which is used to tell what kind of error
+/// happened.
+pub fn record_engine_progress_update(
+    engine: Engine,
+    update: &EngineProgressUpdate,
+    previous: Option<&EngineProgressUpdate>,
+    time_ms: u64,
+) {
+    let mut metrics = METRICS.lock();
+    match update {
+        EngineProgressUpdate::Requesting => {
+            *metrics.engine_requests.entry(engine).or_default() += 1;
+        }
+        EngineProgressUpdate::Done => {
+            metrics
+                .engine_latency
+                .entry(engine)
+                .or_default()
+                .observe(time_ms as f64 / 1000.);
+        }
+        EngineProgressUpdate::Error(_) => {
+            let kind = match previous {
+                Some(EngineProgressUpdate::Downloading) => EngineErrorKind::Download,
+                Some(EngineProgressUpdate::Parsing) => EngineErrorKind::Parse,
+                _ => EngineErrorKind::Request,
+            };
+            *metrics.engine_errors.entry((engine, kind)).or_default() += 1;
+            // failures (including timeouts) are slow too, so they count
+            metrics
+                .engine_latency
+                .entry(engine)
+                .or_default()
+                .observe(time_ms as f64 / 1000.);
+        }
+        EngineProgressUpdate::Downloading | EngineProgressUpdate::Parsing => {}
+    }
+}
+
+pub fn record_engine_results(engine: Engine, count: usize) {
+    *METRICS.lock().engine_results.entry(engine).or_default() += count as u64;
+}
+
+pub fn set_engine_degraded(engine: Engine, degraded: bool) {
+    METRICS.lock().engine_degraded.insert(engine, degraded);
+}
+
+pub fn record_image_cache_lookup(hit: bool) {
+    let mut metrics = METRICS.lock();
+    if hit {
+        metrics.image_cache_hits += 1;
+    } else {
+        metrics.image_cache_misses += 1;
+    }
+}
+
+pub fn record_http_request(method: &str, route: &str, status: u16, latency: Duration) {
+    let mut metrics = METRICS.lock();
+    *metrics
+        .http_requests
+        .entry((method.to_string(), route.to_string(), status))
+        .or_default() += 1;
+    metrics
+        .http_latency
+        .entry((method.to_string(), route.to_string()))
+        .or_default()
+        .observe(latency.as_secs_f64());
+}
+
+/// Render every metric in the Prometheus text format.
+pub fn render() -> String {
+    let metrics = METRICS.lock();
+    let mut out = String::new();
+
+    write_header(
+        &mut out,
+        "metasearch_engine_requests_total",
+        "counter",
+        "Requests made to each engine.",
+    );
+    for (engine, count) in &metrics.engine_requests {
+        let _ = writeln!(
+            out,
+            "metasearch_engine_requests_total{{engine=\"{}\"}} {count}",
+            escape_label(engine.id())
+        );
+    }
+
+    write_header(
+        &mut out,
+        "metasearch_engine_errors_total",
+        "counter",
+        "Failed engine requests, by the step that failed.",
+    );
+    for ((engine, kind), count) in &metrics.engine_errors {
+        let _ = writeln!(
+            out,
+            "metasearch_engine_errors_total{{engine=\"{}\",kind=\"{}\"}} {count}",
+            escape_label(engine.id()),
+            kind.as_str()
+        );
+    }
+
+    write_header(
+        &mut out,
+        "metasearch_engine_latency_seconds",
+        "histogram",
+        "How long engines took to respond or fail, from the start of the search.",
+    );
+    for (engine, histogram) in &metrics.engine_latency {
+        histogram.render(
+            &mut out,
+            "metasearch_engine_latency_seconds",
+            &format!("engine=\"{}\"", escape_label(engine.id())),
+        );
+    }
+
+    write_header(
+        &mut out,
+        "metasearch_engine_results_total",
+        "counter",
+        "Results returned by each engine.",
+    );
+    for (engine, count) in &metrics.engine_results {
+        let _ = writeln!(
+            out,
+            "metasearch_engine_results_total{{engine=\"{}\"}} {count}",
+            escape_label(engine.id())
+        );
+    }
+
+    write_header(
+        &mut out,
+        "metasearch_engine_degraded",
+        "gauge",
+        "Whether the engine failed its last canary check.",
+    );
+    for (engine, degraded) in &metrics.engine_degraded {
+        let _ = writeln!(
+            out,
+            "metasearch_engine_degraded{{engine=\"{}\"}} {}",
+            escape_label(engine.id()),
+            u8::from(*degraded)
+        );
+    }
+
+    write_header(
+        &mut out,
+        "metasearch_image_cache_lookups_total",
+        "counter",
+        "Lookups in the image proxy cache.",
+    );
+    let _ = writeln!(
+        out,
+        "metasearch_image_cache_lookups_total{{result=\"hit\"}} {}",
+        metrics.image_cache_hits
+    );
+    let _ = writeln!(
+        out,
+        "metasearch_image_cache_lookups_total{{result=\"miss\"}} {}",
+        metrics.image_cache_misses
+    );
+
+    write_header(
+        &mut out,
+        "metasearch_http_requests_total",
+        "counter",
+        "HTTP requests, by route and status code.",
+    );
+    for ((method, route, status), count) in &metrics.http_requests {
+        let _ = writeln!(
+            out,
+            "metasearch_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{status}\"}} {count}",
+            escape_label(method),
+            escape_label(route)
+        );
+    }
+
+    write_header(
+        &mut out,
+        "metasearch_http_request_duration_seconds",
+        "histogram",
+        "How long HTTP requests took to respond, by route.",
+    );
+    for ((method, route), histogram) in &metrics.http_latency {
+        histogram.render(
+            &mut out,
+            "metasearch_http_request_duration_seconds",
+            &format!(
+                "method=\"{}\",route=\"{}\"",
+                escape_label(method),
+                escape_label(route)
+            ),
+        );
+    }
+
+    out
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("my \"engine\"\n\\"), r#"my \"engine\"\n\\"#);
+    }
+}
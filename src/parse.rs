@@ -8,6 +8,8 @@ use crate::{
 use scraper::{Html, Selector};
 use tracing::trace;
 
+/// Selectors for pulling search results and a featured snippet out of an
+/// engine's html.
 #[derive(Default)]
 pub struct ParseOpts {
     result: &'static str,
@@ -22,41 +24,48 @@ pub struct ParseOpts {
 }
 
 impl ParseOpts {
+    /// Options that match nothing, to be filled in with the other methods.
     #[must_use]
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// The selector that matches each search result.
     #[must_use]
     pub fn result(mut self, result: &'static str) -> Self {
         self.result = result;
         self
     }
 
+    /// How to get a result's title from its element.
     #[must_use]
     pub fn title(mut self, title: impl Into<QueryMethod>) -> Self {
         self.title = title.into();
         self
     }
 
+    /// How to get a result's url from its element.
     #[must_use]
     pub fn href(mut self, href: impl Into<QueryMethod>) -> Self {
         self.href = href.into();
         self
     }
 
+    /// How to get a result's description from its element.
     #[must_use]
     pub fn description(mut self, description: impl Into<QueryMethod>) -> Self {
         self.description = description.into();
         self
     }
 
+    /// The selector that matches the featured snippet, if the engine has one.
     #[must_use]
     pub fn featured_snippet(mut self, featured_snippet: &'static str) -> Self {
         self.featured_snippet = featured_snippet;
         self
     }
 
+    /// How to get the featured snippet's title from its element.
     #[must_use]
     pub fn featured_snippet_title(
         mut self,
@@ -66,12 +75,14 @@ impl ParseOpts {
         self
     }
 
+    /// How to get the featured snippet's url from its element.
     #[must_use]
     pub fn featured_snippet_href(mut self, featured_snippet_href: impl Into<QueryMethod>) -> Self {
         self.featured_snippet_href = featured_snippet_href.into();
         self
     }
 
+    /// How to get the featured snippet's description from its element.
     #[must_use]
     pub fn featured_snippet_description(
         mut self,
@@ -84,11 +95,15 @@ impl ParseOpts {
 
 type ManualQueryMethod = Box<dyn Fn(&scraper::ElementRef) -> eyre::Result<String>>;
 
+/// A way of getting a string out of an element.
 #[derive(Default)]
 pub enum QueryMethod {
+    /// Always an empty string.
     #[default]
     None,
+    /// The text of the first element that matches the selector.
     CssSelector(&'static str),
+    /// A function that's given the element.
     Manual(ManualQueryMethod),
 }
 
@@ -99,6 +114,7 @@ impl From<&'static str> for QueryMethod {
 }
 
 impl QueryMethod {
+    /// Like [`Self::call`], but with a custom way of handling css selectors.
     pub fn call_with_css_selector_override(
         &self,
         el: &scraper::ElementRef,
@@ -111,6 +127,7 @@ impl QueryMethod {
         }
     }
 
+    /// Get the string from the element.
     pub fn call(&self, el: &scraper::ElementRef) -> eyre::Result<String> {
         self.call_with_css_selector_override(el, |el, s| {
             el.select(&Selector::parse(s).unwrap())
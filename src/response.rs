@@ -0,0 +1,343 @@
+//! Search responses in a format that doesn't change when the internal structs
+//! do. These are used by the versioned JSON API and the command line.
+
+use std::time::Instant;
+
+use scraper::Html;
+use serde::Serialize;
+use tracing::Instrument;
+
+use crate::engines::{
+    self, Engine, EngineProgressUpdate, ProgressUpdateData, ResponseForTab, SearchQuery,
+};
+
+/// The results of a search.
+#[derive(Serialize)]
+pub struct SearchResponse {
+    /// What was searched for.
+    pub query: String,
+    /// The tab that was searched, like `all` or `images`.
+    pub tab: String,
+    /// How long the whole search took.
+    pub time_ms: u64,
+    /// The engines that made a request. Engines that answer instantly (like
+    /// calculators) aren't included.
+    pub engines: Vec<EngineStatus>,
+    /// Which page of the results this is.
+    pub pagination: Pagination,
+    /// The results, best first.
+    pub results: Vec<SearchResult>,
+    /// A result to show above the others.
+    pub featured_snippet: Option<FeaturedSnippet>,
+    /// An answer to the query, like a calculator result.
+    pub answer: Option<RichContent>,
+    /// The infobox from the search results, followed by the one that was found
+    /// after the search (if any).
+    pub infoboxes: Vec<RichContent>,
+}
+
+impl SearchResponse {
+    /// Only keep the results on the given page, starting from 1. A `page` or
+    /// `per_page` of 0 is treated as 1.
+    #[must_use]
+    pub fn paginate(mut self, page: usize, per_page: usize) -> Self {
+        let page = page.max(1);
+        let per_page = per_page.max(1);
+        let total_results = self.results.len();
+        self.results = self
+            .results
+            .into_iter()
+            .skip((page - 1).saturating_mul(per_page))
+            .take(per_page)
+            .collect();
+        self.pagination = Pagination {
+            page,
+            per_page,
+            total_results,
+            total_pages: total_results.div_ceil(per_page),
+        };
+        self
+    }
+}
+
+/// How an engine did in a search.
+#[derive(Serialize)]
+pub struct EngineStatus {
+    /// The id of the engine.
+    pub engine: &'static str,
+    /// Whether it succeeded.
+    pub status: EngineState,
+    /// Only present if the engine failed.
+    pub error: Option<String>,
+    /// How long after the start of the search the engine finished.
+    pub time_ms: u64,
+}
+
+/// Whether an engine succeeded.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineState {
+    /// The engine has its results.
+    Done,
+    /// The engine failed.
+    Error,
+}
+
+/// Engines are only asked for their first page of results, so pages are
+/// slices of the merged results from a single search. Every page that's
+/// requested searches again.
+#[derive(Serialize)]
+pub struct Pagination {
+    /// The page number, starting from 1.
+    pub page: usize,
+    /// How many results are on each page.
+    pub per_page: usize,
+    /// How many results there are on every page combined.
+    pub total_results: usize,
+    /// How many pages there are.
+    pub total_pages: usize,
+}
+
+/// A result that one or more engines found.
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SearchResult {
+    /// A web page, from the `all` tab.
+    Web {
+        /// The url of the page.
+        url: String,
+        /// The title of the page.
+        title: String,
+        /// A snippet of the page, as plain text.
+        description: String,
+        /// The ids of every engine that found it.
+        engines: Vec<&'static str>,
+        /// How it's ranked, higher is better.
+        score: f64,
+    },
+    /// An image, from the `images` tab.
+    Image {
+        /// The url of the full size image.
+        image_url: String,
+        /// The url of the page the image is on.
+        page_url: String,
+        /// The title of the page or image.
+        title: String,
+        /// The width of the image in pixels, or 0 if it's unknown.
+        width: u64,
+        /// The height of the image in pixels, or 0 if it's unknown.
+        height: u64,
+        /// The ids of every engine that found it.
+        engines: Vec<&'static str>,
+        /// How it's ranked, higher is better.
+        score: f64,
+    },
+}
+
+/// A result that an engine shows above the others.
+#[derive(Serialize)]
+pub struct FeaturedSnippet {
+    /// The url of the page.
+    pub url: String,
+    /// The title of the page.
+    pub title: String,
+    /// A snippet of the page, as plain text.
+    pub description: String,
+    /// The id of the engine it's from.
+    pub engine: &'static str,
+}
+
+/// An answer or infobox.
+#[derive(Serialize)]
+pub struct RichContent {
+    /// The id of the engine it's from.
+    pub engine: &'static str,
+    /// The content, as html.
+    pub html: String,
+    /// The text of the HTML, for clients that can't render it.
+    pub text: String,
+}
+
+impl RichContent {
+    fn new(engine: Engine, html: String) -> Self {
+        RichContent {
+            engine: engine.id(),
+            text: html_to_text(&html),
+            html,
+        }
+    }
+}
+
+/// Suggestions for a partly typed query.
+#[derive(Serialize)]
+pub struct AutocompleteResponse {
+    /// The query that was typed.
+    pub query: String,
+    /// The suggestions, best first.
+    pub suggestions: Vec<String>,
+}
+
+/// Search and convert the results to these types. All of the results are
+/// returned, use [`SearchResponse::paginate`] to get one page of them.
+pub async fn search(search_query: SearchQuery) -> eyre::Result<SearchResponse> {
+    let query = search_query.query.clone();
+    let tab = search_query.tab;
+
+    let start_time = Instant::now();
+    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+    let search_future = tokio::spawn(
+        async move { engines::search(&search_query, progress_tx).await }.in_current_span(),
+    );
+
+    let mut engines = Vec::new();
+    let mut response = None;
+    let mut post_search_infobox = None;
+    while let Some(progress_update) = progress_rx.recv().await {
+        match progress_update.data {
+            ProgressUpdateData::Engine { engine, update } => {
+                let (status, error) = match update {
+                    EngineProgressUpdate::Done => (EngineState::Done, None),
+                    EngineProgressUpdate::Error(message) => (EngineState::Error, Some(message)),
+                    _ => continue,
+                };
+                engines.push(EngineStatus {
+                    engine: engine.id(),
+                    status,
+                    error,
+                    time_ms: progress_update.time_ms,
+                });
+            }
+            ProgressUpdateData::Response(r) => response = Some(r),
+            ProgressUpdateData::PostSearchInfobox(infobox) => post_search_infobox = Some(infobox),
+        }
+    }
+    search_future.await??;
+    engines.sort_by_key(|status| status.engine);
+
+    let mut results = Vec::new();
+    let mut featured_snippet = None;
+    let mut answer = None;
+    let mut infoboxes = Vec::new();
+    match response {
+        Some(ResponseForTab::All(r)) => {
+            results = r
+                .search_results
+                .into_iter()
+                .map(|r| SearchResult::Web {
+                    url: r.result.url,
+                    title: r.result.title,
+                    description: r.result.description,
+                    engines: r.engines.iter().map(Engine::id).collect(),
+                    score: r.score,
+                })
+                .collect();
+            featured_snippet = r.featured_snippet.map(|s| FeaturedSnippet {
+                url: s.url,
+                title: s.title,
+                description: s.description,
+                engine: s.engine.id(),
+            });
+            answer = r.answer.map(|a| RichContent::new(a.engine, a.html.0));
+            infoboxes.extend(r.infobox.map(|i| RichContent::new(i.engine, i.html.0)));
+        }
+        Some(ResponseForTab::Images(r)) => {
+            results = r
+                .image_results
+                .into_iter()
+                .map(|r| SearchResult::Image {
+                    image_url: r.result.image_url,
+                    page_url: r.result.page_url,
+                    title: r.result.title,
+                    width: r.result.width,
+                    height: r.result.height,
+                    engines: r.engines.iter().map(Engine::id).collect(),
+                    score: r.score,
+                })
+                .collect();
+        }
+        None => {}
+    }
+    infoboxes.extend(post_search_infobox.map(|i| RichContent::new(i.engine, i.html.0)));
+
+    Ok(SearchResponse {
+        query,
+        tab: tab.to_string(),
+        time_ms: start_time.elapsed().as_millis() as u64,
+        engines,
+        pagination: Pagination {
+            page: 1,
+            per_page: results.len().max(1),
+            total_results: results.len(),
+            total_pages: 1,
+        },
+        results,
+        featured_snippet,
+        answer,
+        infoboxes,
+    })
+}
+
+/// The text content of an HTML fragment, for clients that can't render HTML.
+pub fn html_to_text(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let text = fragment
+        .root_element()
+        .descendants()
+        .filter(|node| {
+            !node
+                .parent()
+                .and_then(|parent| parent.value().as_element())
+                .is_some_and(|parent| matches!(parent.name(), "script" | "style"))
+        })
+        .filter_map(|node| node.value().as_text().map(|text| &text[..]))
+        .collect::<Vec<_>>()
+        .join(" ");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paginate() {
+        let response = SearchResponse {
+            query: "rust".to_string(),
+            tab: "all".to_string(),
+            time_ms: 0,
+            engines: vec![],
+            pagination: Pagination {
+                page: 1,
+                per_page: 3,
+                total_results: 3,
+                total_pages: 1,
+            },
+            results: (0..3)
+                .map(|i| SearchResult::Web {
+                    url: format!("https://example.com/{i}"),
+                    title: i.to_string(),
+                    description: String::new(),
+                    engines: vec![],
+                    score: 1.,
+                })
+                .collect(),
+            featured_snippet: None,
+            answer: None,
+            infoboxes: vec![],
+        };
+
+        let page = response.paginate(0, 0);
+        assert_eq!(page.pagination.page, 1);
+        assert_eq!(page.pagination.per_page, 1);
+        assert_eq!(page.pagination.total_pages, 3);
+        assert!(matches!(&page.results[..], [SearchResult::Web { title, .. }] if title == "0"));
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        assert_eq!(
+            html_to_text("<h2>Title</h2><p>Some <b>bold</b>\n text</p><style>p{}</style>"),
+            "Title Some bold text"
+        );
+    }
+}
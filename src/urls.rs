@@ -1,3 +1,5 @@
+//! Normalizing urls and applying the `[urls]` config to them.
+
 use std::borrow::Cow;
 
 use tracing::{error, warn};
@@ -5,6 +7,7 @@ use url::Url;
 
 use crate::config::{HostAndPath, UrlsConfig};
 
+/// Clean up a url so the same page from different engines can be merged.
 #[tracing::instrument]
 pub fn normalize_url(url: &str) -> String {
     let url = url.trim_end_matches('#');
@@ -74,6 +77,9 @@ pub fn normalize_url(url: &str) -> String {
 }
 
 impl HostAndPath {
+    /// Whether the host and path are matched by this one. A host that starts
+    /// with a dot matches subdomains, and a path that ends with a slash
+    /// matches everything under it.
     pub fn contains(&self, host: &str, path: &str) -> bool {
         if self.host.starts_with('.') {
             if !host.ends_with(&self.host) {
@@ -90,6 +96,7 @@ impl HostAndPath {
         }
     }
 
+    /// The new host and path for `real_url`, if `replace_from` matches it.
     pub fn replace(
         replace_from: &HostAndPath,
         replace_with: &HostAndPath,
@@ -137,6 +144,7 @@ impl HostAndPath {
     }
 }
 
+/// Rewrite the url with the first matching `[urls.replace]` rule.
 pub fn apply_url_replacements(url: &str, urls_config: &UrlsConfig) -> String {
     let Ok(mut url) = Url::parse(url) else {
         error!("failed to parse url");
@@ -163,6 +171,7 @@ pub fn apply_url_replacements(url: &str, urls_config: &UrlsConfig) -> String {
 
     normalize_url(url.as_ref())
 }
+/// The weight of the first matching `[urls.weight]` rule, or 1.
 pub fn get_url_weight(url: &str, urls_config: &UrlsConfig) -> f64 {
     let Ok(url) = Url::parse(url) else {
         error!("failed to parse url");
@@ -0,0 +1,237 @@
+//! Optional instance-wide access control, so the instance can be made private
+//! without needing a separate reverse proxy.
+
+use std::{
+    collections::HashMap,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use base64::Engine as _;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+
+use crate::{
+    config::Config,
+    web::{api_auth, client_ip::ClientIp, search},
+};
+
+/// Credentials that we've already verified, keyed by a hash of the
+/// `Authorization` header. Argon2 is intentionally slow and browsers send the
+/// credentials with every request, so we don't want to redo the work each time.
+static VERIFIED_CREDENTIALS: LazyLock<Mutex<HashMap<[u8; 32], VerifiedCredentials>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+const MAX_VERIFIED_CREDENTIALS: usize = 1000;
+/// How long verified credentials are remembered for, so changing a password in
+/// the config doesn't leave the old one working for long.
+const VERIFIED_CREDENTIALS_TTL: Duration = Duration::from_secs(10 * 60);
+
+struct VerifiedCredentials {
+    username: String,
+    verified_at: Instant,
+}
+
+pub async fn access_middleware(
+    Extension(config): Extension<Config>,
+    ClientIp(ip): ClientIp,
+    req: Request,
+    next: Next,
+) -> Response {
+    let access = &config.access;
+
+    if !access.allowed_networks.is_empty()
+        && !access.allowed_networks.iter().any(|net| net.contains(&ip))
+    {
+        // the ip is in the request span, unless ips are redacted
+        info!("Denied access since the client isn't in an allowed network");
+        return (StatusCode::FORBIDDEN, "Access denied").into_response();
+    }
+
+    if !access.users.is_empty()
+        && !has_api_token(req.uri(), req.headers(), &config)
+        && authenticated_user(req.headers(), &config).await.is_none()
+    {
+        return unauthorized_response(&config);
+    }
+
+    next.run(req).await
+}
+
+/// API clients authenticate with a bearer token instead of basic auth, since
+/// they can't send both in the `Authorization` header. The token's rate limit
+/// and engine restrictions are still applied by the route.
+fn has_api_token(uri: &Uri, headers: &HeaderMap, config: &Config) -> bool {
+    if !config.api.enabled {
+        return false;
+    }
+    // the html pages still need basic auth
+    let is_api_request = match uri.path() {
+        "/search" => search::is_api_request(uri, headers),
+        "/autocomplete" | "/searxng/search" => true,
+        path => path.starts_with("/api/"),
+    };
+    is_api_request && api_auth::has_valid_token(headers, config)
+}
+
+pub fn unauthorized_response(config: &Config) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(
+            header::WWW_AUTHENTICATE,
+            format!(r#"Basic realm="{}", charset="UTF-8""#, config.ui.site_name),
+        )],
+        "Unauthorized",
+    )
+        .into_response()
+}
+
+/// Returns the name of the user from `access.users` that the request's basic
+/// auth credentials are for, if they're valid.
+pub async fn authenticated_user(headers: &HeaderMap, config: &Config) -> Option<String> {
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())?;
+
+    let credentials_hash: [u8; 32] = Sha256::digest(authorization.as_bytes()).into();
+    if let Some(verified) = VERIFIED_CREDENTIALS.lock().get(&credentials_hash) {
+        if verified.verified_at.elapsed() < VERIFIED_CREDENTIALS_TTL {
+            return Some(verified.username.clone());
+        }
+    }
+
+    let (username, password) = authorization
+        .strip_prefix("Basic ")
+        .and_then(|b| base64::engine::general_purpose::STANDARD.decode(b).ok())
+        .and_then(|b| String::from_utf8(b).ok())
+        .and_then(|c| {
+            c.split_once(':')
+                .map(|(u, p)| (u.to_string(), p.to_string()))
+        })?;
+    let Some(password_hash) = config.access.users.get(&username).cloned() else {
+        info!("Denied access to unknown user {username}");
+        return None;
+    };
+
+    let is_valid = tokio::task::spawn_blocking({
+        let username = username.clone();
+        move || {
+            let password_hash = match PasswordHash::new(&password_hash) {
+                Ok(hash) => hash,
+                Err(err) => {
+                    error!("Invalid password hash for user {username}: {err}");
+                    return false;
+                }
+            };
+            Argon2::default()
+                .verify_password(password.as_bytes(), &password_hash)
+                .is_ok()
+        }
+    })
+    .await
+    .unwrap_or_default();
+    if !is_valid {
+        return None;
+    }
+
+    let mut verified_credentials = VERIFIED_CREDENTIALS.lock();
+    if verified_credentials.len() >= MAX_VERIFIED_CREDENTIALS {
+        verified_credentials
+            .retain(|_, verified| verified.verified_at.elapsed() < VERIFIED_CREDENTIALS_TTL);
+    }
+    if verified_credentials.len() >= MAX_VERIFIED_CREDENTIALS {
+        verified_credentials.clear();
+    }
+    verified_credentials.insert(
+        credentials_hash,
+        VerifiedCredentials {
+            username: username.clone(),
+            verified_at: Instant::now(),
+        },
+    );
+    Some(username)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+    use crate::{
+        config::ApiTokenConfig,
+        web::api_auth::{authenticate, ApiAuthError},
+    };
+
+    fn headers(authorization: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static(authorization),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_basic_auth_and_api_tokens() {
+        let mut config = Config::default();
+        config.api.enabled = true;
+        config
+            .access
+            .users
+            .insert("alice".to_string(), "$argon2id$...".to_string());
+        config.api.tokens.insert(
+            "script".to_string(),
+            ApiTokenConfig {
+                token: "secret".to_string(),
+                engines: None,
+                rate_limit: None,
+                expires: None,
+            },
+        );
+
+        // api clients get past the basic auth, but only with a valid token and only
+        // for api requests
+        let bearer = headers("Bearer secret");
+        let uri = |uri: &'static str| Uri::from_static(uri);
+        assert!(has_api_token(&uri("/api/v1/search"), &bearer, &config));
+        assert!(has_api_token(&uri("/autocomplete"), &bearer, &config));
+        assert!(has_api_token(&uri("/searxng/search?q=a"), &bearer, &config));
+        assert!(has_api_token(
+            &uri("/search?q=a&format=json"),
+            &bearer,
+            &config
+        ));
+        assert!(!has_api_token(&uri("/search?q=a"), &bearer, &config));
+        assert!(!has_api_token(&uri("/settings"), &bearer, &config));
+        assert!(!has_api_token(
+            &uri("/search?q=a&format=json"),
+            &headers("Bearer wrong"),
+            &config
+        ));
+        assert!(authenticate(&bearer, &mut config.clone(), "/search").is_ok());
+
+        // basic auth isn't mistaken for a token
+        let basic = headers("Basic YWxpY2U6cGFzc3dvcmQ=");
+        assert!(!has_api_token(
+            &uri("/search?q=a&format=json"),
+            &basic,
+            &config
+        ));
+        assert!(matches!(
+            authenticate(&basic, &mut config.clone(), "/search"),
+            Err(ApiAuthError::MissingToken)
+        ));
+
+        // tokens don't do anything while the api is disabled
+        config.api.enabled = false;
+        assert!(!has_api_token(&uri("/api/v1/search"), &bearer, &config));
+    }
+}
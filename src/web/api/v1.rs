@@ -0,0 +1,119 @@
+use std::{collections::HashMap, str::FromStr};
+
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use tracing::error;
+
+use super::error_response;
+use crate::{
+    config::Config,
+    engines::{self, SearchQuery, SearchTab},
+    response::{self, AutocompleteResponse},
+    web::{api_auth, client_ip::ClientIp},
+};
+
+const DEFAULT_PER_PAGE: usize = 20;
+const MAX_PER_PAGE: usize = 100;
+
+pub async fn search(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(mut config): Extension<Config>,
+    headers: HeaderMap,
+    ClientIp(ip): ClientIp,
+) -> Response {
+    if !config.api.enabled {
+        return error_response(StatusCode::FORBIDDEN, "API access is disabled");
+    }
+    if let Err(err) = api_auth::authenticate(&headers, &mut config, "/api/v1/search") {
+        return err.into_response();
+    }
+
+    let query = params
+        .get("q")
+        .map(|q| q.trim().replace('\n', " "))
+        .unwrap_or_default();
+    if query.is_empty() {
+        return error_response(StatusCode::BAD_REQUEST, "Missing query");
+    }
+    let tab = match params.get("tab").map(|t| SearchTab::from_str(t)) {
+        None => SearchTab::default(),
+        Some(Ok(tab)) => tab,
+        Some(Err(())) => return error_response(StatusCode::BAD_REQUEST, "Unknown tab"),
+    };
+    let Ok(page) = parse_param(&params, "page", 1) else {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid page");
+    };
+    let Ok(per_page) = parse_param(&params, "per_page", DEFAULT_PER_PAGE) else {
+        return error_response(StatusCode::BAD_REQUEST, "Invalid per_page");
+    };
+    let per_page = per_page.clamp(1, MAX_PER_PAGE);
+
+    let search_query = SearchQuery {
+        query,
+        tab,
+        request_headers: headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or_default().to_string()))
+            .collect(),
+        ip: ip.to_string(),
+        config: config.into(),
+    };
+
+    let logging = search_query.config.logging.clone();
+    let query = search_query.query.clone();
+    match response::search(search_query).await {
+        Ok(response) => Json(response.paginate(page, per_page)).into_response(),
+        Err(err) => {
+            error!("Search error: {}", logging.loggable_error(&err, &query));
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+        }
+    }
+}
+
+pub async fn autocomplete(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(mut config): Extension<Config>,
+    headers: HeaderMap,
+) -> Response {
+    if !config.api.enabled {
+        return error_response(StatusCode::FORBIDDEN, "API access is disabled");
+    }
+    if let Err(err) = api_auth::authenticate(&headers, &mut config, "/api/v1/autocomplete") {
+        return err.into_response();
+    }
+
+    let query = params
+        .get("q")
+        .map(|q| q.replace('\n', " "))
+        .unwrap_or_default();
+    match engines::autocomplete(&config, &query).await {
+        Ok(suggestions) => Json(AutocompleteResponse { query, suggestions }).into_response(),
+        Err(err) => {
+            error!(
+                "Autocomplete error for {}: {}",
+                config.logging.loggable_query(&query),
+                config.logging.loggable_error(&err, &query)
+            );
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+        }
+    }
+}
+
+pub async fn openapi() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        include_str!("openapi.json"),
+    )
+}
+
+fn parse_param(
+    params: &HashMap<String, String>,
+    name: &str,
+    default: usize,
+) -> Result<usize, std::num::ParseIntError> {
+    params.get(name).map_or(Ok(default), |value| value.parse())
+}
@@ -0,0 +1,158 @@
+//! Checking the bearer tokens that are used to access the API.
+
+use std::{
+    collections::HashMap,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::config::{ApiTokenConfig, Config};
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// The start of the current rate limit window and how many requests have been
+/// made in it, keyed by token name.
+static RATE_LIMITS: LazyLock<Mutex<HashMap<String, (Instant, u32)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Check the `Authorization` header of an API request, and apply the token's
+/// engine restrictions to the config.
+///
+/// If no tokens are configured then every request is allowed.
+pub fn authenticate(
+    headers: &HeaderMap,
+    config: &mut Config,
+    route: &str,
+) -> Result<(), ApiAuthError> {
+    if config.api.tokens.is_empty() {
+        return Ok(());
+    }
+
+    // other schemes (like the basic auth from `access.users`) aren't for us
+    let Some(bearer) = bearer_token(headers) else {
+        return Err(ApiAuthError::MissingToken);
+    };
+
+    let Some((name, token)) = find_token(config, bearer) else {
+        warn!("API request to {route} with an invalid token");
+        return Err(ApiAuthError::InvalidToken);
+    };
+
+    if has_expired(token) {
+        info!("API request to {route} from expired token {name}");
+        return Err(ApiAuthError::Expired);
+    }
+
+    if let Some(rate_limit) = token.rate_limit {
+        let mut rate_limits = RATE_LIMITS.lock();
+        let (window_start, count) = rate_limits
+            .entry(name.clone())
+            .or_insert((Instant::now(), 0));
+        if window_start.elapsed() >= RATE_LIMIT_WINDOW {
+            *window_start = Instant::now();
+            *count = 0;
+        }
+        if *count >= rate_limit {
+            let retry_after = RATE_LIMIT_WINDOW.saturating_sub(window_start.elapsed());
+            info!("API request to {route} from rate limited token {name}");
+            return Err(ApiAuthError::RateLimited { retry_after });
+        }
+        *count += 1;
+    }
+
+    info!("API request to {route} from token {name}");
+
+    let token = token.clone();
+    restrict_engines(config, &token);
+
+    Ok(())
+}
+
+/// Whether the request has a token that's configured and hasn't expired. This
+/// doesn't count towards the token's rate limit.
+pub fn has_valid_token(headers: &HeaderMap, config: &Config) -> bool {
+    bearer_token(headers)
+        .and_then(|bearer| find_token(config, bearer))
+        .is_some_and(|(_, token)| !has_expired(token))
+}
+
+/// The token from the `Authorization` header, if it uses the `Bearer` scheme.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let authorization = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = authorization.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then_some(token.trim())
+}
+
+fn find_token<'a>(config: &'a Config, bearer: &str) -> Option<(&'a String, &'a ApiTokenConfig)> {
+    config
+        .api
+        .tokens
+        .iter()
+        .find(|(_, token)| constant_time_eq(token.token.as_bytes(), bearer.as_bytes()))
+}
+
+fn has_expired(token: &ApiTokenConfig) -> bool {
+    token.expires.is_some_and(|expires| Utc::now() >= expires.0)
+}
+
+#[derive(Debug)]
+pub enum ApiAuthError {
+    MissingToken,
+    InvalidToken,
+    Expired,
+    RateLimited { retry_after: Duration },
+}
+
+impl IntoResponse for ApiAuthError {
+    fn into_response(self) -> Response {
+        match self {
+            ApiAuthError::MissingToken => (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                "Missing API token",
+            )
+                .into_response(),
+            ApiAuthError::InvalidToken => {
+                (StatusCode::UNAUTHORIZED, "Invalid API token").into_response()
+            }
+            ApiAuthError::Expired => {
+                (StatusCode::UNAUTHORIZED, "API token has expired").into_response()
+            }
+            ApiAuthError::RateLimited { retry_after } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, (retry_after.as_secs() + 1).to_string())],
+                "Rate limit exceeded",
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Disable every engine that the token isn't allowed to use.
+fn restrict_engines(config: &mut Config, token: &ApiTokenConfig) {
+    let Some(allowed_engines) = &token.engines else {
+        return;
+    };
+
+    let mut engines = config.engines.as_ref().clone();
+    engines.restrict_to(allowed_engines);
+    config.engines = engines.into();
+}
+
+/// Compare the hashes of the values, so the time it takes doesn't depend on
+/// where they differ or on how long the token is.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let (a, b) = (Sha256::digest(a), Sha256::digest(b));
+    a.iter().zip(&b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
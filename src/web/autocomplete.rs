@@ -1,14 +1,24 @@
 use std::collections::HashMap;
 
-use axum::{extract::Query, http::StatusCode, response::IntoResponse, Extension, Json};
+use axum::{
+    extract::Query,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
 use tracing::error;
 
-use crate::{config::Config, engines};
+use crate::{config::Config, engines, web::api_auth};
 
 pub async fn route(
     Query(params): Query<HashMap<String, String>>,
-    Extension(config): Extension<Config>,
-) -> impl IntoResponse {
+    Extension(mut config): Extension<Config>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    if let Some(res) = access_error(&headers, &mut config) {
+        return res;
+    }
+
     let query = params
         .get("q")
         .cloned()
@@ -18,10 +28,74 @@ pub async fn route(
     let res = match engines::autocomplete(&config, &query).await {
         Ok(res) => res,
         Err(err) => {
-            error!("Autocomplete error for {query}: {err}");
-            return (StatusCode::INTERNAL_SERVER_ERROR, Json((query, vec![])));
+            error!(
+                "Autocomplete error for {}: {}",
+                config.logging.loggable_query(&query),
+                config.logging.loggable_error(&err, &query)
+            );
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json((query, Vec::<String>::new())),
+            )
+                .into_response();
         }
     };
 
-    (StatusCode::OK, Json((query, res)))
+    (StatusCode::OK, Json((query, res))).into_response()
+}
+
+/// Autocomplete is public by default, since the search bar and browsers
+/// (through opensearch) can't send a token. Tokens are still checked if
+/// they're sent, so their rate limits apply.
+fn access_error(headers: &HeaderMap, config: &mut Config) -> Option<Response> {
+    let has_token = api_auth::bearer_token(headers).is_some();
+    if config.api.public_autocomplete && !(config.api.enabled && has_token) {
+        return None;
+    }
+    if !config.api.enabled {
+        return Some((StatusCode::FORBIDDEN, "API access is disabled").into_response());
+    }
+    api_auth::authenticate(headers, config, "/autocomplete")
+        .err()
+        .map(IntoResponse::into_response)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+    use crate::config::ApiTokenConfig;
+
+    #[test]
+    fn test_private_autocomplete_needs_token() {
+        let mut config = Config::default();
+        config.api.enabled = true;
+        config.api.public_autocomplete = false;
+        config.api.tokens.insert(
+            "script".to_string(),
+            ApiTokenConfig {
+                token: "secret".to_string(),
+                engines: None,
+                rate_limit: None,
+                expires: None,
+            },
+        );
+
+        // anyone can send the headers that browsers do
+        let mut headers = HeaderMap::new();
+        headers.insert("sec-fetch-site", HeaderValue::from_static("same-origin"));
+        let res = access_error(&headers, &mut config.clone()).unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        headers.insert("authorization", HeaderValue::from_static("Bearer secret"));
+        assert!(access_error(&headers, &mut config.clone()).is_none());
+
+        config.api.enabled = false;
+        let res = access_error(&headers, &mut config.clone()).unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        config.api.public_autocomplete = true;
+        assert!(access_error(&HeaderMap::new(), &mut config).is_none());
+    }
 }
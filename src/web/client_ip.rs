@@ -0,0 +1,228 @@
+//! Figuring out the real IP of the client, taking trusted reverse proxies into
+//! account.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use axum::{
+    extract::{connect_info::Connected, ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap, StatusCode},
+    serve::IncomingStream,
+};
+use ipnet::IpNet;
+use tokio::net::TcpListener;
+
+use crate::config::Config;
+
+/// Who connected to us. Clients connecting through a Unix socket don't have an
+/// address, so they're treated as localhost.
+#[derive(Debug, Clone)]
+pub enum PeerAddr {
+    Tcp(SocketAddr),
+    Unix,
+}
+
+impl Connected<IncomingStream<'_, TcpListener>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        PeerAddr::Tcp(*stream.remote_addr())
+    }
+}
+
+impl Connected<IncomingStream<'_, super::tls::TlsListener>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_, super::tls::TlsListener>) -> Self {
+        PeerAddr::Tcp(*stream.remote_addr())
+    }
+}
+
+#[cfg(unix)]
+impl Connected<IncomingStream<'_, tokio::net::UnixListener>> for PeerAddr {
+    fn connect_info(_stream: IncomingStream<'_, tokio::net::UnixListener>) -> Self {
+        PeerAddr::Unix
+    }
+}
+
+/// The IP of the client that made the request.
+///
+/// If the request came from one of the `trusted_proxies` in the config (or
+/// through a Unix socket, if `trust_unix_socket` is set), then the `Forwarded`
+/// or `X-Forwarded-For` headers are used to find the real client.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(ConnectInfo(peer)) = parts.extensions.get::<ConnectInfo<PeerAddr>>() else {
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Missing connection info"));
+        };
+        let config = parts.extensions.get::<Config>();
+        let trusted_proxies = config
+            .map(|config| config.trusted_proxies.as_slice())
+            .unwrap_or_default();
+        let trust_unix_socket = config.is_some_and(|config| config.trust_unix_socket);
+
+        Ok(ClientIp(match peer {
+            PeerAddr::Tcp(addr) => resolve(addr.ip(), &parts.headers, trusted_proxies),
+            PeerAddr::Unix => resolve_unix(&parts.headers, trusted_proxies, trust_unix_socket),
+        }))
+    }
+}
+
+pub fn resolve(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpNet]) -> IpAddr {
+    // ipv4 clients show up as ipv4-mapped ipv6 addresses if we're listening on [::]
+    let peer = peer.to_canonical();
+    if !trusted_proxies.iter().any(|net| net.contains(&peer)) {
+        return peer;
+    }
+    resolve_forwarded(peer, headers, trusted_proxies)
+}
+
+/// Anyone who can connect to the socket could send forwarding headers, so they're
+/// only used if the config says that the socket is trusted. Otherwise (or if
+/// there aren't any forwarding headers) it's a local client.
+fn resolve_unix(headers: &HeaderMap, trusted_proxies: &[IpNet], trust_unix_socket: bool) -> IpAddr {
+    let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    if !trust_unix_socket {
+        return localhost;
+    }
+    resolve_forwarded(localhost, headers, trusted_proxies)
+}
+
+/// Find the client from the forwarding headers, given that the peer that
+/// connected to us is a proxy we trust.
+fn resolve_forwarded(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+
+    // walk backwards through the hops, starting from the one that connected to our
+    // proxy. the first one that isn't a proxy we trust is the client, since
+    // everything before that could've been made up by the client.
+    let mut client = peer;
+    for hop in forwarded_hops(headers).into_iter().rev() {
+        let Some(hop) = hop else {
+            // one of our proxies gave us something like `for=unknown`
+            break;
+        };
+        client = hop.to_canonical();
+        if !is_trusted(&client) {
+            break;
+        }
+    }
+    client
+}
+
+/// Returns the addresses from the `Forwarded` header, or `X-Forwarded-For` if
+/// that isn't present. The closest hop is last. Hops that aren't IPs (like
+/// `unknown` or obfuscated identifiers) are `None`.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let forwarded = headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>();
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .flat_map(|v| v.split(','))
+            .filter_map(|element| {
+                element.split(';').find_map(|pair| {
+                    let (key, value) = pair.trim().split_once('=')?;
+                    key.eq_ignore_ascii_case("for").then_some(value)
+                })
+            })
+            .map(parse_forwarded_node)
+            .collect();
+    }
+
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(parse_forwarded_node)
+        .collect()
+}
+
+/// Parse a node like `192.0.2.43`, `"192.0.2.43:47011"` or
+/// `"[2001:db8:cafe::17]:4711"`.
+fn parse_forwarded_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    // ipv6 in brackets without a port
+    node.strip_prefix('[')
+        .and_then(|n| n.strip_suffix(']'))
+        .and_then(|n| n.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve_with(peer: &str, headers: &[(&'static str, &str)], trusted: &[&str]) -> String {
+        let mut header_map = HeaderMap::new();
+        for (key, value) in headers {
+            header_map.append(*key, value.parse().unwrap());
+        }
+        let trusted = trusted
+            .iter()
+            .map(|n| n.parse().unwrap())
+            .collect::<Vec<_>>();
+        resolve(peer.parse().unwrap(), &header_map, &trusted).to_string()
+    }
+
+    #[test]
+    fn test_untrusted_peer_is_client() {
+        let ip = resolve_with("203.0.113.7", &[("x-forwarded-for", "1.1.1.1")], &[]);
+        assert_eq!(ip, "203.0.113.7");
+    }
+
+    #[test]
+    fn test_spoofed_hops_are_ignored() {
+        let ip = resolve_with(
+            "10.0.0.2",
+            &[("x-forwarded-for", "1.1.1.1, 203.0.113.7, 10.0.0.1")],
+            &["10.0.0.0/8"],
+        );
+        assert_eq!(ip, "203.0.113.7");
+    }
+
+    #[test]
+    fn test_forwarded_header() {
+        let ip = resolve_with(
+            "127.0.0.1",
+            &[
+                ("forwarded", r#"for="[2001:db8:cafe::17]:4711";proto=https"#),
+                ("forwarded", "for=192.0.2.43:47011"),
+                // forwarded takes priority
+                ("x-forwarded-for", "1.1.1.1"),
+            ],
+            &["127.0.0.1/32", "192.0.2.0/24"],
+        );
+        assert_eq!(ip, "2001:db8:cafe::17");
+    }
+
+    #[test]
+    fn test_unix_socket_is_only_trusted_when_configured() {
+        let mut headers = HeaderMap::new();
+        headers.append("x-forwarded-for", "1.1.1.1".parse().unwrap());
+        assert_eq!(resolve_unix(&headers, &[], false).to_string(), "127.0.0.1");
+        assert_eq!(resolve_unix(&headers, &[], true).to_string(), "1.1.1.1");
+    }
+
+    #[test]
+    fn test_unknown_hop() {
+        let ip = resolve_with(
+            "127.0.0.1",
+            &[("forwarded", "for=unknown")],
+            &["127.0.0.1/32"],
+        );
+        assert_eq!(ip, "127.0.0.1");
+    }
+}
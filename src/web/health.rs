@@ -0,0 +1,65 @@
+//! Endpoints for orchestrators (like Kubernetes) to check whether we're alive
+//! and ready to serve searches.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde_json::json;
+
+use crate::{
+    config::Config,
+    engines::{health as engine_health, Engine},
+    web::index::{COMMIT_HASH, VERSION},
+};
+
+/// Set once the listener is bound.
+pub static LISTENING: AtomicBool = AtomicBool::new(false);
+/// Set once we've been told to shut down, so we stop getting sent new
+/// requests while the old ones finish.
+pub static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+pub async fn healthz() -> Response {
+    Json(json!({
+        "status": "ok",
+        "version": VERSION,
+        "commit": COMMIT_HASH,
+    }))
+    .into_response()
+}
+
+pub async fn readyz(Extension(config): Extension<Config>) -> Response {
+    let listening = LISTENING.load(Ordering::Relaxed);
+    let shutting_down = SHUTTING_DOWN.load(Ordering::Relaxed);
+
+    let (healthy_engines, failing_engines): (Vec<Engine>, Vec<Engine>) = Engine::all()
+        .into_iter()
+        .filter(|engine| engine.is_search_engine() && config.engines.get(*engine).enabled)
+        .partition(|&engine| !engine_health::is_failing(engine, &config.circuit_breaker));
+
+    let ready =
+        listening && !shutting_down && healthy_engines.len() >= config.readiness.min_engines;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(json!({
+            "status": if ready { "ready" } else { "not ready" },
+            "version": VERSION,
+            "commit": COMMIT_HASH,
+            "listening": listening,
+            "shutting_down": shutting_down,
+            "healthy_engines": healthy_engines,
+            "failing_engines": failing_engines,
+            "min_engines": config.readiness.min_engines,
+        })),
+    )
+        .into_response()
+}
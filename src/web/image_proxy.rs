@@ -1,4 +1,10 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
 
 use axum::{
     extract::Query,
@@ -6,18 +12,186 @@ use axum::{
     response::{IntoResponse, Response},
     Extension,
 };
+use base64::Engine as _;
+use hmac::{Hmac, Mac};
+use image::{
+    codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage,
+};
+use scraper::{Html, Node, Selector};
+use sha2::Sha256;
 use tracing::error;
-use wreq::header;
+use url::{Host, Url};
+use wreq::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    header, redirect,
+};
+use wreq_util::Emulation;
+
+use crate::config::{Config, ImageProxyConfig, ThumbnailFormat};
+
+mod cache;
+
+pub use cache::init as init_cache;
+
+const MAX_REDIRECTS: usize = 5;
+
+/// A separate client for the image proxy that refuses to connect to anything
+/// that isn't on the public internet, so the proxy can't be used to make
+/// requests to our internal network.
+static CLIENT: LazyLock<wreq::Client> = LazyLock::new(|| {
+    wreq::ClientBuilder::new()
+        .local_address(IpAddr::from_str("0.0.0.0").unwrap())
+        .emulation(Emulation::Firefox139)
+        .timeout(Duration::from_secs(10))
+        // proxies would do the dns resolution for us, so we wouldn't be able to check the ip
+        .no_proxy()
+        .dns_resolver(Arc::new(PublicOnlyResolver))
+        .redirect(redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if !is_allowed_url(attempt.url()) {
+                attempt.error("redirected to a disallowed url")
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()
+        .unwrap()
+});
+
+/// Get the URL that should be used to load an image through our proxy.
+pub fn proxy_url(image_url: &str, config: &Config) -> String {
+    let escaped_url =
+        url::form_urlencoded::byte_serialize(image_url.as_bytes()).collect::<String>();
+    let signature = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .encode(signature_mac(image_url, config).finalize().into_bytes());
+    format!("/image-proxy?url={escaped_url}&sig={signature}")
+}
+
+/// Rewrite the `src` and `srcset` of every `<img>` and `<source>` in the HTML
+/// so they load through our proxy. This is used for answers and infoboxes,
+/// since they can contain images from anywhere.
+pub fn proxy_images_in_html(html: &str, config: &Config) -> String {
+    static IMAGE_SELECTOR: LazyLock<Selector> =
+        LazyLock::new(|| Selector::parse("img, source").unwrap());
+
+    if !config.image_search.proxy.enabled {
+        return html.to_string();
+    }
+
+    let mut fragment = Html::parse_fragment(html);
+    let image_ids = fragment
+        .select(&IMAGE_SELECTOR)
+        .map(|image| image.id())
+        .collect::<Vec<_>>();
+    for id in image_ids {
+        let Some(mut node) = fragment.tree.get_mut(id) else {
+            continue;
+        };
+        let Node::Element(element) = node.value() else {
+            continue;
+        };
+        for (name, value) in &mut element.attrs {
+            let proxied = match &*name.local {
+                "src" => proxy_src(value, config),
+                "srcset" => proxy_srcset(value, config),
+                _ => continue,
+            };
+            *value = proxied.into();
+        }
+    }
+    fragment.root_element().inner_html()
+}
+
+/// Anything that isn't an http url (like data urls) is left alone.
+fn proxy_src(url: &str, config: &Config) -> String {
+    let url = url.trim();
+    if url.starts_with("http://") || url.starts_with("https://") {
+        proxy_url(url, config)
+    } else if let Some(url) = url.strip_prefix("//") {
+        proxy_url(&format!("https://{url}"), config)
+    } else {
+        url.to_string()
+    }
+}
+
+/// srcset is a list of urls followed by optional descriptors, like
+/// `a.png 1x, b.png 2x`. Urls can have commas in them, so this is parsed like
+/// browsers do it (see the html spec for "parse a srcset attribute").
+fn proxy_srcset(srcset: &str, config: &Config) -> String {
+    let mut candidates = Vec::new();
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if rest.is_empty() {
+            break;
+        }
+        let url_end = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let (url, after_url) = rest.split_at(url_end);
 
-use crate::{config::Config, engines};
+        let descriptor;
+        let url = if let Some(url) = url.strip_suffix(',') {
+            // a comma at the end of the url means it doesn't have a descriptor
+            descriptor = "";
+            rest = after_url;
+            url.trim_end_matches(',')
+        } else {
+            // commas in parentheses don't end the descriptor
+            let mut depth = 0;
+            let descriptor_end = after_url
+                .find(|c: char| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        ',' if depth <= 0 => return true,
+                        _ => {}
+                    }
+                    false
+                })
+                .unwrap_or(after_url.len());
+            descriptor = after_url[..descriptor_end].trim();
+            rest = &after_url[descriptor_end..];
+            url
+        };
+
+        let url = proxy_src(url, config);
+        if descriptor.is_empty() {
+            candidates.push(url);
+        } else {
+            candidates.push(format!("{url} {descriptor}"));
+        }
+    }
+    candidates.join(", ")
+}
+
+fn signature_mac(image_url: &str, config: &Config) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(config.image_search.proxy.secret.as_bytes())
+        .expect("hmac can take keys of any size");
+    mac.update(image_url.as_bytes());
+    mac
+}
+
+fn has_valid_signature(image_url: &str, signature: &str, config: &Config) -> bool {
+    let Ok(signature) = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(signature) else {
+        return false;
+    };
+    signature_mac(image_url, config)
+        .verify_slice(&signature)
+        .is_ok()
+}
 
 pub async fn route(
     Query(params): Query<HashMap<String, String>>,
     Extension(config): Extension<Config>,
 ) -> Response {
-    let image_search_config = &config.image_search;
-    let proxy_config = &image_search_config.proxy;
-    if !image_search_config.enabled || !proxy_config.enabled {
+    let proxy_config = &config.image_search.proxy;
+    // the proxy is also used for images in answers and infoboxes, so it doesn't
+    // depend on image search being enabled
+    if !proxy_config.enabled {
         return (StatusCode::FORBIDDEN, "Image proxy is disabled").into_response();
     };
     let url = params.get("url").cloned().unwrap_or_default();
@@ -25,15 +199,30 @@ pub async fn route(
         return (StatusCode::BAD_REQUEST, "Missing `url` parameter").into_response();
     }
 
-    let mut res = match engines::CLIENT
-        .get(&url)
-        .header("accept", "image/*")
-        .send()
-        .await
-    {
+    let signature = params.get("sig").map(String::as_str).unwrap_or_default();
+    if !has_valid_signature(&url, signature, &config) {
+        return (StatusCode::FORBIDDEN, "Invalid signature").into_response();
+    }
+
+    // ip hosts don't go through our dns resolver, so they have to be checked here
+    if !Url::parse(&url).is_ok_and(|url| is_allowed_url(&url)) {
+        return (StatusCode::FORBIDDEN, "URL not allowed").into_response();
+    }
+
+    let cache_key = cache::key(&url, &transform_description(proxy_config));
+    if let Some(cached) = cache::get(&cache_key).await {
+        return image_response(cached.content_type, cached.bytes);
+    }
+
+    let mut res = match CLIENT.get(&url).header("accept", "image/*").send().await {
         Ok(res) => res,
         Err(err) => {
-            error!("Image proxy error for {url}: {err}");
+            let logging = &config.logging;
+            error!(
+                "Image proxy error for {}: {}",
+                logging.loggable_error(&url, ""),
+                logging.loggable_error(&err, "")
+            );
             return (StatusCode::INTERNAL_SERVER_ERROR, "Image proxy error").into_response();
         }
     };
@@ -72,6 +261,39 @@ pub async fn route(
         }
     }
 
+    let (content_type, image_bytes) = if proxy_config.max_width.is_some() && subtype != "gif" {
+        let proxy_config = proxy_config.clone();
+        let loggable_url = config.logging.loggable_error(&url, "");
+        let resized = tokio::task::spawn_blocking(move || {
+            match resize(&image_bytes, &proxy_config) {
+                Ok(Some(resized)) => resized,
+                // it was already small enough
+                Ok(None) => (content_type, image_bytes),
+                Err(err) => {
+                    error!("Couldn't resize image from {loggable_url}: {err}");
+                    (content_type, image_bytes)
+                }
+            }
+        })
+        .await;
+        match resized {
+            Ok(resized) => resized,
+            Err(err) => {
+                // the image decoder panicked
+                error!("Couldn't resize image: {err}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Image proxy error").into_response();
+            }
+        }
+    } else {
+        (content_type, image_bytes)
+    };
+
+    cache::insert(&cache_key, &content_type, &image_bytes).await;
+
+    image_response(content_type, image_bytes)
+}
+
+fn image_response(content_type: String, image_bytes: Vec<u8>) -> Response {
     (
         [
             (header::CONTENT_TYPE, content_type),
@@ -83,3 +305,226 @@ pub async fn route(
     )
         .into_response()
 }
+
+/// A description of how images are changed before being sent, so cached
+/// images are invalidated when the config changes.
+fn transform_description(config: &ImageProxyConfig) -> String {
+    match config.max_width {
+        Some(max_width) => format!("{max_width}:{:?}:{}", config.format, config.quality),
+        None => String::new(),
+    }
+}
+
+/// Shrink the image so it's at most `max_width` pixels wide, returning the new
+/// content type and bytes. Returns `None` if the image didn't need to be
+/// resized.
+fn resize(
+    image_bytes: &[u8],
+    config: &ImageProxyConfig,
+) -> image::ImageResult<Option<(String, Vec<u8>)>> {
+    let Some(max_width) = config.max_width else {
+        return Ok(None);
+    };
+    let image = image::load_from_memory(image_bytes)?;
+    if image.width() <= max_width {
+        return Ok(None);
+    }
+    let image = image.resize(max_width, u32::MAX, FilterType::Triangle);
+
+    let mut resized_bytes = Vec::new();
+    let content_type = match config.format {
+        ThumbnailFormat::Jpeg => {
+            // jpeg doesn't support transparency
+            DynamicImage::from(image.to_rgb8()).write_with_encoder(
+                JpegEncoder::new_with_quality(&mut resized_bytes, config.quality.clamp(1, 100)),
+            )?;
+            "image/jpeg"
+        }
+        ThumbnailFormat::Webp => {
+            DynamicImage::from(image.to_rgba8())
+                .write_with_encoder(WebPEncoder::new_lossless(&mut resized_bytes))?;
+            "image/webp"
+        }
+    };
+    Ok(Some((content_type.to_string(), resized_bytes)))
+}
+
+/// Resolves hostnames like normal, but leaves out any addresses that aren't
+/// public.
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect::<Vec<_>>();
+            if addrs.is_empty() {
+                return Err(format!("{host} doesn't resolve to any public addresses").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn is_allowed_url(url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(_)) => true,
+        Some(Host::Ipv4(ip)) => is_public_ip(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => is_public_ip(IpAddr::V6(ip)),
+        None => false,
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // 0.0.0.0/8, "this network"
+        || a == 0
+        // 100.64.0.0/10, carrier-grade nat
+        || (a == 100 && (b & 0b1100_0000) == 64)
+        // 198.18.0.0/15, benchmarking
+        || (a == 198 && (b & 0b1111_1110) == 18)
+        // 240.0.0.0/4, reserved
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local()
+        // 64:ff9b::/96, nat64 could be used to reach private ipv4 addresses
+        || ip.segments()[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+        // ::/96, ipv4-compatible addresses are deprecated and can embed any ipv4
+        || ip.segments()[..6] == [0, 0, 0, 0, 0, 0]
+        // 2002::/16, 6to4 has an ipv4 address after the prefix
+        || ip.segments()[0] == 0x2002
+        // 2001::/32, teredo has the server's ipv4 address after the prefix
+        || ip.segments()[..2] == [0x2001, 0]
+        // 2001:db8::/32, documentation
+        || ip.segments()[..2] == [0x2001, 0xdb8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_ips() {
+        for ip in ["1.1.1.1", "93.184.215.14", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip} should be public");
+        }
+    }
+
+    #[test]
+    fn test_non_public_ips() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a00:1",
+            "::a00:1",
+            "2002:a00:1::",
+            "2001:0:a00:1::",
+        ] {
+            assert!(
+                !is_public_ip(ip.parse().unwrap()),
+                "{ip} shouldn't be public"
+            );
+        }
+    }
+
+    #[test]
+    fn test_proxy_images_in_html() {
+        let config = Config::default();
+        let html = proxy_images_in_html(
+            r#"<p><img alt="a" src="https://example.com/a.png?x=1&amp;y=2" srcset="https://example.com/a.png 1x, https://example.com/b.png 2x"><img src="data:image/png;base64,AAAA"></p>"#,
+            &config,
+        );
+        let a = html_escape::encode_double_quoted_attribute(&proxy_url(
+            "https://example.com/a.png?x=1&y=2",
+            &config,
+        ))
+        .to_string();
+        assert!(html.contains(&format!(r#"src="{a}""#)), "{html}");
+        assert!(html.contains(" 2x\""), "{html}");
+        assert!(!html.contains(r#"srcset="https://"#), "{html}");
+        assert!(
+            html.contains(r#"src="data:image/png;base64,AAAA""#),
+            "{html}"
+        );
+
+        // attributes that aren't double quoted, and images in <picture>
+        let html = proxy_images_in_html(
+            r#"<img src='https://example.com/a.png'><img src=https://example.com/b.png><picture><source srcset="//example.com/c.webp"><img src="/d.png"></picture>"#,
+            &config,
+        );
+        assert!(!html.contains(r#"="https://"#), "{html}");
+        assert!(!html.contains(r#"="//"#), "{html}");
+        assert_eq!(html.matches("/image-proxy?").count(), 3, "{html}");
+    }
+
+    #[test]
+    fn test_srcset_with_commas() {
+        let config = Config::default();
+        let a = "https://cdn.example.com/w_200,h_100/a.png";
+        let b = "https://cdn.example.com/w_400,h_200/a.png";
+        assert_eq!(
+            proxy_srcset(&format!("{a} 1x,{b} 2x"), &config),
+            format!("{} 1x, {} 2x", proxy_url(a, &config), proxy_url(b, &config))
+        );
+        // a comma at the end of a url means it doesn't have a descriptor
+        assert_eq!(
+            proxy_srcset(&format!("{a},\n{b} 2x"), &config),
+            format!("{}, {} 2x", proxy_url(a, &config), proxy_url(b, &config))
+        );
+    }
+
+    #[test]
+    fn test_signature() {
+        let config = Config::default();
+        let image_url = "https://example.com/image.png";
+        let proxied = Url::parse(&format!(
+            "http://localhost{}",
+            proxy_url(image_url, &config)
+        ))
+        .unwrap();
+        let params = proxied.query_pairs().collect::<HashMap<_, _>>();
+        assert_eq!(params["url"], image_url);
+        assert!(has_valid_signature(image_url, &params["sig"], &config));
+        assert!(!has_valid_signature(
+            "http://169.254.169.254/",
+            &params["sig"],
+            &config
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+//! A content-addressed on-disk cache for the image proxy, so images that were
+//! already proxied don't have to be downloaded and resized again.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::SystemTime,
+};
+
+use lru::LruCache;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+
+use crate::{config::ImageCacheConfig, metrics};
+
+static CACHE: OnceLock<ImageCache> = OnceLock::new();
+
+struct ImageCache {
+    path: PathBuf,
+    max_size: u64,
+    state: Mutex<CacheState>,
+}
+
+struct CacheState {
+    /// The size of every cached file, keyed by the cache key. The least
+    /// recently used entries get evicted first.
+    entries: LruCache<String, u64>,
+    total_size: u64,
+}
+
+pub struct CachedImage {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Set up the cache and read the images that are already in it. This does
+/// nothing if the cache is disabled.
+pub fn init(config: &ImageCacheConfig) {
+    if !config.enabled {
+        return;
+    }
+
+    if let Err(err) = fs::create_dir_all(&config.path) {
+        error!(
+            "Couldn't create image cache directory {}: {err}",
+            config.path.display()
+        );
+        return;
+    }
+
+    // the least recently modified files go in first, so they get evicted first
+    let mut files = existing_files(&config.path);
+    files.sort_by_key(|(_, _, modified)| *modified);
+
+    let mut entries = LruCache::unbounded();
+    let mut total_size = 0;
+    for (key, size, _) in files {
+        total_size += size;
+        entries.put(key, size);
+    }
+    info!(
+        "Loaded {} images ({total_size} bytes) from the image cache",
+        entries.len()
+    );
+
+    let cache = ImageCache {
+        path: config.path.clone(),
+        max_size: config.max_size,
+        state: Mutex::new(CacheState {
+            entries,
+            total_size,
+        }),
+    };
+    // this runs on startup before we're serving anything, so blocking is fine
+    remove_files(cache.evict());
+    let _ = CACHE.set(cache);
+}
+
+/// The cache key for an image. `transform` should describe how the image was
+/// changed (i.e. resized), so different versions of the same image don't
+/// collide.
+pub fn key(url: &str, transform: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(url.as_bytes());
+    hasher.update([0]);
+    hasher.update(transform.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+pub async fn get(key: &str) -> Option<CachedImage> {
+    let cache = CACHE.get()?;
+    // this also marks it as recently used
+    let is_cached = cache.state.lock().entries.get(key).is_some();
+    metrics::record_image_cache_lookup(is_cached);
+    if !is_cached {
+        return None;
+    }
+
+    let data = match tokio::fs::read(cache.file_path(key)).await {
+        Ok(data) => data,
+        Err(err) => {
+            error!("Couldn't read {key} from the image cache: {err}");
+            cache.remove(key);
+            return None;
+        }
+    };
+    let newline = data.iter().position(|&b| b == b'\n')?;
+    let content_type = String::from_utf8(data[..newline].to_vec()).ok()?;
+    let bytes = data[newline + 1..].to_vec();
+    Some(CachedImage {
+        content_type,
+        bytes,
+    })
+}
+
+pub async fn insert(key: &str, content_type: &str, bytes: &[u8]) {
+    let Some(cache) = CACHE.get() else {
+        return;
+    };
+
+    let mut data = Vec::with_capacity(content_type.len() + 1 + bytes.len());
+    data.extend_from_slice(content_type.as_bytes());
+    data.push(b'\n');
+    data.extend_from_slice(bytes);
+    let size = data.len() as u64;
+    if size > cache.max_size {
+        return;
+    }
+
+    let path = cache.file_path(key);
+    // write to a temporary file first so a half-written image is never served.
+    // the name is random since the same image can be written by two requests at
+    // once
+    let tmp_path = path.with_extension(format!("{:016x}.tmp", rand::random::<u64>()));
+    let write = async {
+        tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+        tokio::fs::write(&tmp_path, &data).await?;
+        tokio::fs::rename(&tmp_path, &path).await
+    };
+    if let Err(err) = write.await {
+        error!("Couldn't write {key} to the image cache: {err}");
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return;
+    }
+
+    {
+        let mut state = cache.state.lock();
+        if let Some(old_size) = state.entries.put(key.to_string(), size) {
+            state.total_size -= old_size;
+        }
+        state.total_size += size;
+    }
+    let evicted = cache.evict();
+    if !evicted.is_empty() {
+        let _ = tokio::task::spawn_blocking(move || remove_files(evicted)).await;
+    }
+}
+
+impl ImageCache {
+    fn file_path(&self, key: &str) -> PathBuf {
+        // files are split into subdirectories so we don't end up with one huge
+        // directory
+        self.path.join(&key[..2]).join(key)
+    }
+
+    fn remove(&self, key: &str) {
+        let mut state = self.state.lock();
+        if let Some(size) = state.entries.pop(key) {
+            state.total_size -= size;
+        }
+    }
+
+    /// Forget the least recently used images until the cache fits in
+    /// `max_size`, and return the paths of the files that should be deleted.
+    /// The files aren't deleted here so we don't block while holding the lock.
+    fn evict(&self) -> Vec<PathBuf> {
+        let mut state = self.state.lock();
+        let mut evicted = Vec::new();
+        while state.total_size > self.max_size {
+            let Some((key, size)) = state.entries.pop_lru() else {
+                break;
+            };
+            state.total_size -= size;
+            evicted.push(self.file_path(&key));
+        }
+        evicted
+    }
+}
+
+fn remove_files(paths: Vec<PathBuf>) {
+    for path in paths {
+        if let Err(err) = fs::remove_file(&path) {
+            error!(
+                "Couldn't remove {} from the image cache: {err}",
+                path.display()
+            );
+        }
+    }
+}
+
+/// Whether the name is one of our keys (a hex sha256 hash), so other files
+/// that end up in the cache directory are ignored.
+fn is_valid_key(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Returns the key, size and modification time of every file in the cache
+/// directory.
+fn existing_files(path: &Path) -> Vec<(String, u64, SystemTime)> {
+    let mut files = Vec::new();
+    let Ok(dirs) = fs::read_dir(path) else {
+        return files;
+    };
+    for entry in dirs
+        .flatten()
+        .flat_map(|dir| fs::read_dir(dir.path()))
+        .flatten()
+    {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let path = entry.path();
+        if path.extension().is_some() {
+            // leftover temporary file
+            let _ = fs::remove_file(&path);
+            continue;
+        }
+        let (Some(key), Ok(metadata)) = (
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .filter(|n| is_valid_key(n))
+                .map(str::to_string),
+            entry.metadata(),
+        ) else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        files.push((key, metadata.len(), modified));
+    }
+    files
+}
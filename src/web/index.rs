@@ -4,8 +4,8 @@ use maud::{html, PreEscaped, DOCTYPE};
 use crate::{config::Config, web::head_html};
 
 const BASE_COMMIT_URL: &str = "https://github.com/mat-1/metasearch2/commit/";
-const VERSION: &str = std::env!("CARGO_PKG_VERSION");
-const COMMIT_HASH: &str = std::env!("GIT_HASH");
+pub const VERSION: &str = std::env!("CARGO_PKG_VERSION");
+pub const COMMIT_HASH: &str = std::env!("GIT_HASH");
 const COMMIT_HASH_SHORT: &str = std::env!("GIT_HASH_SHORT");
 
 pub async fn get(Extension(config): Extension<Config>) -> impl IntoResponse {
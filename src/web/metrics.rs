@@ -0,0 +1,73 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info, Span};
+
+use crate::{config::Config, metrics};
+
+pub async fn route() -> impl IntoResponse {
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        metrics::render(),
+    )
+}
+
+/// Record the latency and status code of every request.
+pub async fn http_metrics_middleware(req: Request, next: Next) -> Response {
+    let start_time = Instant::now();
+    let method = req.method().to_string();
+    // using the matched route instead of the real path keeps the number of label
+    // values small
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    Span::current().record("route", &route);
+
+    let res = next.run(req).await;
+
+    metrics::record_http_request(&method, &route, res.status().as_u16(), start_time.elapsed());
+    res
+}
+
+/// Serve the metrics on their own address, if one is configured. The returned
+/// task finishes once the server has shut down.
+pub async fn serve_separately(
+    config: &Config,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Option<JoinHandle<()>> {
+    let bind_addr = config.metrics.bind?;
+
+    let app = Router::new().route("/metrics", get(route));
+    let listener = match tokio::net::TcpListener::bind(bind_addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Couldn't bind metrics listener to {bind_addr}: {err}");
+            return None;
+        }
+    };
+    info!("Serving metrics on http://{bind_addr}/metrics");
+    Some(tokio::spawn(async move {
+        let shutdown = async move {
+            let _ = shutdown_rx.wait_for(|&shutting_down| shutting_down).await;
+        };
+        if let Err(err) = axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown)
+            .await
+        {
+            error!("Metrics server error: {err}");
+        }
+    }))
+}
@@ -1,25 +1,49 @@
+mod access;
+pub mod api;
+mod api_auth;
 mod autocomplete;
-mod image_proxy;
+pub mod client_ip;
+mod health;
+pub mod image_proxy;
 mod index;
+mod metrics;
 mod opensearch;
+mod request_id;
 mod search;
 mod settings;
+mod stats;
+mod tls;
 
-use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use std::{
+    convert::Infallible,
+    fmt, fs,
+    future::Future,
+    io,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 
 use axum::{
-    extract::{Request, State},
-    http::{header, StatusCode},
+    extract::{connect_info::Connected, FromRequestParts, Request, State},
+    http::{header, HeaderMap, StatusCode},
     middleware::{self, Next},
     response::Response,
     routing::{get, post, MethodRouter},
+    serve::{IncomingStream, Listener},
     Router,
 };
 use axum_extra::extract::CookieJar;
+use futures::future::join_all;
 use maud::{html, Markup, PreEscaped};
-use tracing::info;
+use tokio::{sync::watch, task::JoinHandle};
+use tokio_rustls::TlsAcceptor;
+use tracing::{error, info, warn, Span};
 
-use crate::config::Config;
+use crate::{
+    config::{BindAddress, Config},
+    engines,
+    web::client_ip::{ClientIp, PeerAddr},
+};
 
 macro_rules! register_static_routes {
     ( $app:ident, $( $x:expr ),* ) => {
@@ -40,10 +64,28 @@ macro_rules! register_static_routes {
 }
 
 pub async fn run(config: Config) {
-    let bind_addr = config.bind;
+    let bind_addresses = config.bind.clone();
+    let unix_socket_permissions = config.unix_socket_permissions;
+    let grace_period = Duration::from_secs(config.shutdown_grace_period_secs);
+    let tls_config = config.tls.clone();
+    let tls_acceptor = if tls_config.enabled {
+        match tls::acceptor(&tls_config) {
+            Ok(acceptor) => Some(acceptor),
+            Err(err) => {
+                error!("Couldn't set up TLS: {err}");
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
+    image_proxy::init_cache(&config.image_search.proxy.cache);
 
     let config = Arc::new(config);
 
+    engines::canary::spawn(config.clone());
+
     fn static_route<S>(
         content: &'static str,
         content_type: &'static str,
@@ -55,19 +97,45 @@ pub async fn run(config: Config) {
         get(|| async { response })
     }
 
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/", get(index::get))
         .route("/search", get(search::get))
+        .route("/searxng/search", get(search::get_searxng))
         .route("/settings", get(settings::get))
         .route("/settings", post(settings::post))
         .route("/opensearch.xml", get(opensearch::route))
         .route("/autocomplete", get(autocomplete::route))
         .route("/image-proxy", get(image_proxy::route))
+        .route("/stats", get(stats::get))
+        .route("/api/v1/search", get(api::v1::search))
+        .route("/api/v1/autocomplete", get(api::v1::autocomplete))
+        .route("/api/v1/openapi.json", get(api::v1::openapi));
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+
+    let mut servers = Vec::new();
+    if config.metrics.enabled {
+        if config.metrics.bind.is_some() {
+            servers.extend(metrics::serve_separately(&config, shutdown_rx.clone()).await);
+        } else {
+            app = app.route("/metrics", get(metrics::route));
+        }
+    }
+    let app = app
+        // static routes are added after the middleware, so they don't require access
+        .layer(middleware::from_fn(access::access_middleware))
         .layer(middleware::from_fn_with_state(
             config.clone(),
             config_middleware,
         ))
-        .with_state(config);
+        .with_state(config.clone());
+    // these are added after the access middleware so orchestrators can always use
+    // them, but they still need the config
+    let app = app.merge(
+        Router::new()
+            .route("/healthz", get(health::healthz))
+            .route("/readyz", get(health::readyz))
+            .layer(middleware::from_fn_with_state(config, config_middleware)),
+    );
     let app = register_static_routes![
         app,
         "style.css",
@@ -80,16 +148,170 @@ pub async fn run(config: Config) {
         "themes/nord-bluish.css",
         "themes/discord.css"
     ];
+    let app = app
+        .layer(middleware::from_fn(metrics::http_metrics_middleware))
+        .layer(middleware::from_fn(request_id::request_id_middleware));
 
-    info!("Listening on http://{bind_addr}");
+    for bind_address in &bind_addresses {
+        match listen(
+            bind_address,
+            unix_socket_permissions,
+            tls_acceptor.as_ref(),
+            app.clone(),
+            shutdown_rx.clone(),
+        )
+        .await
+        {
+            Ok(server) => servers.push(server),
+            Err(err) => {
+                error!("Couldn't listen on {bind_address}: {err}");
+                return;
+            }
+        }
+    }
+    if let Some(redirect_bind) = tls_config.redirect_bind.filter(|_| tls_config.enabled) {
+        // redirect to the port of the first address we're serving https on
+        let https_port = bind_addresses.iter().find_map(|address| match address {
+            BindAddress::Tcp(addr) => Some(addr.port()),
+            BindAddress::Unix(_) => None,
+        });
+        match tokio::net::TcpListener::bind(redirect_bind).await {
+            Ok(listener) => {
+                info!("Redirecting http://{redirect_bind} to https");
+                let mut shutdown_rx = shutdown_rx.clone();
+                servers.push(tokio::spawn(async move {
+                    let shutdown = async move {
+                        let _ = shutdown_rx.wait_for(|&shutting_down| shutting_down).await;
+                    };
+                    let app = tls::redirect_router(https_port.unwrap_or(443));
+                    if let Err(err) = axum::serve(listener, app)
+                        .with_graceful_shutdown(shutdown)
+                        .await
+                    {
+                        error!("Redirect server error: {err}");
+                    }
+                }));
+            }
+            Err(err) => {
+                error!("Couldn't listen on {redirect_bind}: {err}");
+                return;
+            }
+        }
+    }
+    health::LISTENING.store(true, Ordering::Relaxed);
+
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutting down, waiting up to {grace_period:?} for requests to finish");
+        health::SHUTTING_DOWN.store(true, Ordering::Relaxed);
+        let _ = shutdown_tx.send(true);
+    });
+
+    let grace_period_over = async {
+        let _ = shutdown_rx.wait_for(|&shutting_down| shutting_down).await;
+        tokio::time::sleep(grace_period).await;
+    };
 
-    let listener = tokio::net::TcpListener::bind(bind_addr).await.unwrap();
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await
-    .unwrap();
+    tokio::select! {
+        _ = join_all(servers) => {}
+        _ = grace_period_over => {
+            warn!("Requests didn't finish in the grace period, stopping anyways");
+        }
+    }
+}
+
+/// Start serving the app on the address. The returned task finishes once the
+/// server has shut down.
+async fn listen(
+    bind_address: &BindAddress,
+    unix_socket_permissions: Option<u32>,
+    tls_acceptor: Option<&TlsAcceptor>,
+    app: Router,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> io::Result<JoinHandle<()>> {
+    let shutdown = async move {
+        let _ = shutdown_rx.wait_for(|&shutting_down| shutting_down).await;
+    };
+
+    match bind_address {
+        BindAddress::Tcp(addr) => {
+            if let Some(acceptor) = tls_acceptor {
+                let listener = tls::TlsListener::bind(*addr, acceptor.clone()).await?;
+                info!("Listening on https://{addr}");
+                return Ok(tokio::spawn(serve(listener, app, shutdown)));
+            }
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            info!("Listening on {bind_address}");
+            Ok(tokio::spawn(serve(listener, app, shutdown)))
+        }
+        #[cfg(unix)]
+        BindAddress::Unix(path) => {
+            use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+            // a socket that was left behind last time would make binding fail
+            if fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                fs::remove_file(path)?;
+            }
+            let listener = tokio::net::UnixListener::bind(path)?;
+            if let Some(mode) = unix_socket_permissions {
+                fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+            }
+            info!("Listening on {bind_address}");
+
+            let path = path.clone();
+            Ok(tokio::spawn(async move {
+                serve(listener, app, shutdown).await;
+                let _ = fs::remove_file(path);
+            }))
+        }
+        #[cfg(not(unix))]
+        BindAddress::Unix(_) => {
+            let _ = (unix_socket_permissions, app, shutdown);
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix sockets aren't supported on this platform",
+            ))
+        }
+    }
+}
+
+async fn serve<L>(listener: L, app: Router, shutdown: impl Future<Output = ()> + Send + 'static)
+where
+    L: Listener,
+    L::Addr: fmt::Debug,
+    PeerAddr: for<'a> Connected<IncomingStream<'a, L>>,
+{
+    let make_service = app.into_make_service_with_connect_info::<PeerAddr>();
+    if let Err(err) = axum::serve(listener, make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+    {
+        error!("Server error: {err}");
+    }
+}
+
+/// Resolves when we get SIGINT (ctrl+c) or SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for ctrl+c");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
 
 fn guess_mime_type(path: &str) -> &'static str {
@@ -117,12 +339,36 @@ async fn config_middleware(
         }
     }
 
+    let redact_ips = config.logging.redact_queries;
     // modify the state
     req.extensions_mut().insert(config);
 
+    // the request span is made before we have the config, so the client's ip is
+    // added to it here (after the trusted proxies are known)
+    if !redact_ips {
+        let (mut parts, body) = req.into_parts();
+        if let Ok(ClientIp(ip)) = ClientIp::from_request_parts(&mut parts, &()).await {
+            Span::current().record("client_ip", tracing::field::display(ip));
+        }
+        req = Request::from_parts(parts, body);
+    }
+
     Ok(next.run(req).await)
 }
 
+/// The URL of the instance that links to it should use, without a trailing
+/// slash.
+pub fn base_url(config: &Config, headers: &HeaderMap) -> String {
+    if let Some(public_url) = &config.public_url {
+        return public_url.trim_end_matches('/').to_string();
+    }
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("localhost");
+    format!("https://{host}")
+}
+
 pub fn head_html(title: Option<&str>, config: &Config) -> Markup {
     html! {
         head {
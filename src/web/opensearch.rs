@@ -1,14 +1,18 @@
 use axum::{
     http::{header, HeaderMap},
     response::IntoResponse,
+    Extension,
 };
 use maud::{html, PreEscaped};
 
-pub async fn route(headers: HeaderMap) -> impl IntoResponse {
-    let host = headers
-        .get("host")
-        .and_then(|host| host.to_str().ok())
-        .unwrap_or("localhost");
+use crate::{config::Config, web::base_url};
+
+pub async fn route(Extension(config): Extension<Config>, headers: HeaderMap) -> impl IntoResponse {
+    // feed readers can't send tokens, so the feed is only advertised if anyone can
+    // use it
+    let has_public_feed = config.api.enabled && config.api.tokens.is_empty();
+
+    let base_url = base_url(&config, &headers);
 
     (
         [(
@@ -21,8 +25,13 @@ pub async fn route(headers: HeaderMap) -> impl IntoResponse {
                 ShortName { "metasearch" }
                 Description { "Search metasearch" }
                 InputEncoding { "UTF-8" }
-                Url type="text/html" method="get" template=(format!("https://{host}/search?q={{searchTerms}}")) {}
-                Url type="application/x-suggestions+json" method="get" template=(format!("https://{host}/autocomplete?q={{searchTerms}}")) {}
+                Url type="text/html" method="get" template=(format!("{base_url}/search?q={{searchTerms}}")) {}
+                @if has_public_feed {
+                    Url type="application/rss+xml" method="get" template=(format!("{base_url}/search?q={{searchTerms}}&format=rss")) {}
+                }
+                @if config.api.public_autocomplete {
+                    Url type="application/x-suggestions+json" method="get" template=(format!("{base_url}/autocomplete?q={{searchTerms}}")) {}
+                }
             }
         }.into_string(),
     )
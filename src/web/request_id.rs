@@ -0,0 +1,35 @@
+//! Giving every request a random ID, so all of the logs for one request can be
+//! found together.
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{info_span, Instrument};
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+pub async fn request_id_middleware(req: Request, next: Next) -> Response {
+    let request_id = format!("{:016x}", rand::random::<u64>());
+
+    // everything that's logged while handling the request (including the search
+    // that it might start) is in this span
+    let span = info_span!(
+        "request",
+        id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+        // filled in once the request is routed
+        route = tracing::field::Empty,
+        // filled in once the config is read, unless ips are redacted
+        client_ip = tracing::field::Empty,
+    );
+    let mut res = next.run(req).instrument(span).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER.clone(), value);
+    }
+    res
+}
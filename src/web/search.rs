@@ -1,18 +1,22 @@
 mod all;
+mod feed;
 mod images;
+mod searxng;
+mod stream;
 
-use std::{collections::HashMap, net::SocketAddr, str::FromStr};
+use std::{collections::HashMap, net::IpAddr, str::FromStr};
 
 use async_stream::stream;
 use axum::{
     body::Body,
-    extract::{ConnectInfo, Query},
-    http::{header, HeaderMap, StatusCode},
+    extract::Query,
+    http::{header, HeaderMap, StatusCode, Uri},
     response::IntoResponse,
     Extension, Json,
 };
 use bytes::Bytes;
 use maud::{html, PreEscaped, DOCTYPE};
+use tracing::{Instrument, Span};
 
 use crate::{
     config::Config,
@@ -20,8 +24,10 @@ use crate::{
         self, Engine, EngineProgressUpdate, ProgressUpdateData, ResponseForTab, SearchQuery,
         SearchTab,
     },
-    web::head_html,
+    web::{api_auth, base_url, client_ip::ClientIp, head_html},
 };
+use feed::FeedFormat;
+use stream::StreamFormat;
 
 fn render_beginning_of_html(search: &SearchQuery) -> String {
     let form_html = html! {
@@ -107,7 +113,12 @@ pub fn render_engine_list(engines: &[engines::Engine], config: &Config) -> PreEs
         } else {
             raw_engine_id.to_string()
         };
-        html.push_str(&html! { span.engine-list-item { (engine_id) } }.into_string())
+        let html_item = if engines::health::is_degraded(*engine) {
+            html! { span.engine-list-item.degraded title="This engine might be broken" { (engine_id) } }
+        } else {
+            html! { span.engine-list-item { (engine_id) } }
+        };
+        html.push_str(&html_item.into_string())
     }
     html! {
         div.engine-list {
@@ -116,11 +127,82 @@ pub fn render_engine_list(engines: &[engines::Engine], config: &Config) -> PreEs
     }
 }
 
+enum ApiFormat {
+    /// The results are sent once the search is done.
+    Json,
+    Stream(StreamFormat),
+    Feed(FeedFormat),
+    /// The same JSON that SearXNG returns, for `/searxng/search`.
+    Searxng,
+}
+
+/// The machine-readable format that the search was requested in, or `None` for
+/// the html page.
+fn api_format(params: &HashMap<String, String>, headers: &HeaderMap) -> Option<ApiFormat> {
+    let format = params.get("format").map(String::as_str);
+    // feeds are machine-readable search results too, so they're gated like the api
+    if let Some(feed_format) = format.and_then(|format| FeedFormat::from_str(format).ok()) {
+        return Some(ApiFormat::Feed(feed_format));
+    }
+    let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok());
+    match format {
+        Some("json") => Some(ApiFormat::Json),
+        Some("sse") => Some(ApiFormat::Stream(StreamFormat::Sse)),
+        Some("ndjson") => Some(ApiFormat::Stream(StreamFormat::Ndjson)),
+        _ => match accept {
+            Some("application/json") => Some(ApiFormat::Json),
+            Some("text/event-stream") => Some(ApiFormat::Stream(StreamFormat::Sse)),
+            Some("application/x-ndjson") => Some(ApiFormat::Stream(StreamFormat::Ndjson)),
+            _ => None,
+        },
+    }
+}
+
+/// Whether a request to `/search` is for the api instead of the html page.
+pub fn is_api_request(uri: &Uri, headers: &HeaderMap) -> bool {
+    Query::<HashMap<String, String>>::try_from_uri(uri)
+        .is_ok_and(|Query(params)| api_format(&params, headers).is_some())
+}
+
 pub async fn get(
     Query(params): Query<HashMap<String, String>>,
     Extension(config): Extension<Config>,
     headers: HeaderMap,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(ip): ClientIp,
+) -> axum::response::Response {
+    let api_format = api_format(&params, &headers);
+    respond(params, config, headers, ip, api_format, "/search").await
+}
+
+/// SearXNG's `/search`, so tools that were made for it can use
+/// `https://<host>/searxng` as the instance url.
+pub async fn get_searxng(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(config): Extension<Config>,
+    headers: HeaderMap,
+    ClientIp(ip): ClientIp,
+) -> axum::response::Response {
+    if !config.api.searxng_compatible {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    respond(
+        params,
+        config,
+        headers,
+        ip,
+        Some(ApiFormat::Searxng),
+        "/searxng/search",
+    )
+    .await
+}
+
+async fn respond(
+    params: HashMap<String, String>,
+    mut config: Config,
+    headers: HeaderMap,
+    ip: IpAddr,
+    api_format: Option<ApiFormat>,
+    route: &str,
 ) -> axum::response::Response {
     let query = params
         .get("q")
@@ -146,7 +228,7 @@ pub async fn get(
         .and_then(|t| SearchTab::from_str(t).ok())
         .unwrap_or_default();
 
-    let query = SearchQuery {
+    let mut query = SearchQuery {
         query,
         tab: search_tab,
         request_headers: headers
@@ -159,29 +241,33 @@ pub async fn get(
                 )
             })
             .collect(),
-        ip: headers
-            // this could be exploited under some setups, but the ip is only used for the
-            // "what is my ip" answer so it doesn't really matter
-            .get("x-forwarded-for")
-            .map_or_else(
-                || addr.ip().to_string(),
-                |ip| ip.to_str().unwrap_or_default().to_string(),
-            ),
+        ip: ip.to_string(),
         config: config.clone().into(),
     };
 
-    let trying_to_use_api = query
-        .request_headers
-        .get("accept")
-        .is_some_and(|accept| accept == "application/json")
-        || params.get("format").is_some_and(|format| format == "json");
-    if trying_to_use_api {
-        if !config.api {
+    if let Some(api_format) = api_format {
+        if !config.api.enabled {
             return (StatusCode::FORBIDDEN, "API access is disabled").into_response();
         }
+        if let Err(err) = api_auth::authenticate(&headers, &mut config, route) {
+            return err.into_response();
+        }
+        query.config = config.into();
+
+        match api_format {
+            ApiFormat::Stream(stream_format) => return stream::response(query, stream_format),
+            ApiFormat::Feed(feed_format) => {
+                let base_url = base_url(&query.config, &headers);
+                return feed::response(query, feed_format, &base_url).await;
+            }
+            ApiFormat::Searxng => return searxng::response(query).await,
+            ApiFormat::Json => {}
+        }
 
         let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
-        let search_future = tokio::spawn(async move { engines::search(&query, progress_tx).await });
+        let search_future = tokio::spawn(
+            async move { engines::search(&query, progress_tx).await }.in_current_span(),
+        );
         if let Err(e) = search_future.await {
             return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
         }
@@ -196,6 +282,9 @@ pub async fn get(
         return Json(results).into_response();
     }
 
+    // the stream is polled after we return, so the search has to be put in the
+    // request's span manually
+    let span = Span::current();
     let s = stream! {
         type R = Result<Bytes, eyre::Error>;
 
@@ -213,7 +302,9 @@ pub async fn get(
 
         let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
 
-        let search_future = tokio::spawn(async move { engines::search(&query, progress_tx).await });
+        let search_future = tokio::spawn(
+            async move { engines::search(&query, progress_tx).await }.instrument(span),
+        );
 
         while let Some(progress_update) = progress_rx.recv().await {
             match progress_update.data {
@@ -5,7 +5,7 @@ use maud::{html, PreEscaped};
 use crate::{
     config::Config,
     engines::{self, EngineSearchResult, Infobox, Response},
-    web::search::render_engine_list,
+    web::{image_proxy, search::render_engine_list},
 };
 
 pub fn render_results(response: Response) -> PreEscaped<String> {
@@ -14,7 +14,7 @@ pub fn render_results(response: Response) -> PreEscaped<String> {
         html.push_str(
             &html! {
                 div.answer {
-                    (answer.html)
+                    (PreEscaped(image_proxy::proxy_images_in_html(&answer.html.0, &response.config)))
                     (render_engine_list(&[answer.engine], &response.config))
                 }
             }
@@ -25,7 +25,7 @@ pub fn render_results(response: Response) -> PreEscaped<String> {
         html.push_str(
             &html! {
                 div.infobox {
-                    (infobox.html)
+                    (PreEscaped(image_proxy::proxy_images_in_html(&infobox.html.0, &response.config)))
                     (render_engine_list(&[infobox.engine], &response.config))
                 }
             }
@@ -86,7 +86,7 @@ fn render_featured_snippet(
 pub fn render_infobox(infobox: &Infobox, config: &Config) -> PreEscaped<String> {
     html! {
         div.infobox.postsearch-infobox {
-            (infobox.html)
+            (PreEscaped(image_proxy::proxy_images_in_html(&infobox.html.0, config)))
             (render_engine_list(&[infobox.engine], config))
         }
     }
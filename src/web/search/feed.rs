@@ -0,0 +1,230 @@
+//! Rendering search results as RSS or Atom feeds, so queries can be subscribed
+//! to in feed readers.
+
+use std::str::FromStr;
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use maud::{html, Markup, PreEscaped};
+use tracing::Instrument;
+
+use crate::engines::{self, Engine, ProgressUpdateData, ResponseForTab, SearchQuery};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+}
+
+impl FromStr for FeedFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rss" => Ok(Self::Rss),
+            "atom" => Ok(Self::Atom),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A search result in a form that both feed formats can use.
+struct FeedItem {
+    title: String,
+    url: String,
+    description: String,
+    engines: Vec<Engine>,
+}
+
+/// `base_url` is where the instance is, like `https://search.example.com`.
+pub async fn response(query: SearchQuery, format: FeedFormat, base_url: &str) -> Response {
+    let search_url = format!(
+        "{base_url}/search?q={}&tab={}",
+        urlencoding::encode(&query.query),
+        query.tab
+    );
+    let title = format!("{}: {}", query.config.ui.site_name, query.query);
+    let query_string = query.query.clone();
+
+    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+    let search_future =
+        tokio::spawn(async move { engines::search(&query, progress_tx).await }.in_current_span());
+    match search_future.await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+
+    let mut items = Vec::new();
+    while let Some(progress_update) = progress_rx.recv().await {
+        match progress_update.data {
+            ProgressUpdateData::Response(ResponseForTab::All(r)) => {
+                items.extend(r.search_results.into_iter().map(|r| FeedItem {
+                    title: r.result.title,
+                    url: r.result.url,
+                    description: r.result.description,
+                    engines: r.engines.into_iter().collect(),
+                }));
+            }
+            ProgressUpdateData::Response(ResponseForTab::Images(r)) => {
+                items.extend(r.image_results.into_iter().map(|r| FeedItem {
+                    title: r.result.title,
+                    url: r.result.page_url,
+                    description: r.result.image_url,
+                    engines: r.engines.into_iter().collect(),
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    let (content_type, body) = match format {
+        FeedFormat::Rss => (
+            "application/rss+xml; charset=utf-8",
+            render_rss(&title, &query_string, &search_url, base_url, &items),
+        ),
+        FeedFormat::Atom => (
+            "application/atom+xml; charset=utf-8",
+            render_atom(&title, &search_url, base_url, &items),
+        ),
+    };
+    ([(header::CONTENT_TYPE, content_type)], body.into_string()).into_response()
+}
+
+fn render_rss(
+    title: &str,
+    query: &str,
+    search_url: &str,
+    base_url: &str,
+    items: &[FeedItem],
+) -> Markup {
+    html! {
+        (PreEscaped(r#"<?xml version="1.0" encoding="utf-8"?>"#))
+        rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom" xmlns:opensearch="http://a9.com/-/spec/opensearch/1.1/" {
+            channel {
+                title { (title) }
+                link { (search_url) }
+                description { "Search results for " (query) }
+                lastBuildDate { (Utc::now().to_rfc2822()) }
+                opensearch:totalResults { (items.len()) }
+                opensearch:Query role="request" searchTerms=(query) {}
+                atom:link rel="search" type="application/opensearchdescription+xml" href=(format!("{base_url}/opensearch.xml")) {}
+                @for item in items {
+                    item {
+                        title { (item.title) }
+                        link { (item.url) }
+                        guid isPermaLink="true" { (item.url) }
+                        description { (item.description) }
+                        @for engine in &item.engines {
+                            category { (engine.id()) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn render_atom(title: &str, search_url: &str, base_url: &str, items: &[FeedItem]) -> Markup {
+    // search results don't have dates, so everything was updated now
+    let now = Utc::now().to_rfc3339();
+    html! {
+        (PreEscaped(r#"<?xml version="1.0" encoding="utf-8"?>"#))
+        feed xmlns="http://www.w3.org/2005/Atom" xmlns:opensearch="http://a9.com/-/spec/opensearch/1.1/" {
+            title { (title) }
+            id { (search_url) }
+            updated { (now) }
+            link rel="alternate" type="text/html" href=(search_url) {}
+            link rel="search" type="application/opensearchdescription+xml" href=(format!("{base_url}/opensearch.xml")) {}
+            author { name { (base_url) } }
+            opensearch:totalResults { (items.len()) }
+            @for item in items {
+                entry {
+                    title { (item.title) }
+                    id { (item.url) }
+                    link href=(item.url) {}
+                    updated { (now) }
+                    summary { (item.description) }
+                    @for engine in &item.engines {
+                        category term=(engine.id()) {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items() -> Vec<FeedItem> {
+        vec![FeedItem {
+            title: "Fish & <Chips>".to_string(),
+            url: "https://example.com/?a=1&b=2".to_string(),
+            description: "Tasty".to_string(),
+            engines: vec![Engine::Bing, Engine::Google],
+        }]
+    }
+
+    #[test]
+    fn test_render_rss() {
+        let rss = render_rss(
+            "metasearch: fish & chips",
+            "fish & chips",
+            "https://search.example.com/search?q=fish%20%26%20chips&tab=all",
+            "https://search.example.com",
+            &items(),
+        )
+        .into_string();
+        assert!(
+            rss.contains("<title>Fish &amp; &lt;Chips&gt;</title>"),
+            "{rss}"
+        );
+        assert!(
+            rss.contains("<link>https://example.com/?a=1&amp;b=2</link>"),
+            "{rss}"
+        );
+        assert!(
+            rss.contains("<category>bing</category><category>google</category>"),
+            "{rss}"
+        );
+        assert!(rss.contains(r#"searchTerms="fish &amp; chips""#), "{rss}");
+        assert!(
+            rss.contains(r#"href="https://search.example.com/opensearch.xml""#),
+            "{rss}"
+        );
+    }
+
+    #[test]
+    fn test_render_atom() {
+        let atom = render_atom(
+            "metasearch: fish & chips",
+            "https://search.example.com/search?q=fish%20%26%20chips&tab=all",
+            "https://search.example.com",
+            &items(),
+        )
+        .into_string();
+        assert!(
+            atom.contains("<title>metasearch: fish &amp; chips</title>"),
+            "{atom}"
+        );
+        assert!(
+            atom.contains("<title>Fish &amp; &lt;Chips&gt;</title>"),
+            "{atom}"
+        );
+        assert!(
+            atom.contains(r#"href="https://example.com/?a=1&amp;b=2""#),
+            "{atom}"
+        );
+        assert!(
+            atom.contains(
+                r#"<category term="bing"></category><category term="google"></category>"#
+            ),
+            "{atom}"
+        );
+    }
+}
@@ -3,7 +3,7 @@ use maud::{html, PreEscaped};
 use crate::{
     config::Config,
     engines::{self, EngineImageResult, ImagesResponse},
-    web::search::render_engine_list,
+    web::{image_proxy, search::render_engine_list},
 };
 
 pub fn render_results(response: ImagesResponse) -> PreEscaped<String> {
@@ -22,10 +22,7 @@ fn render_image_result(
 ) -> PreEscaped<String> {
     let original_image_src = &result.result.image_url;
     let image_src = if config.image_search.proxy.enabled {
-        // serialize url params
-        let escaped_param =
-            url::form_urlencoded::byte_serialize(original_image_src.as_bytes()).collect::<String>();
-        format!("/image-proxy?url={escaped_param}")
+        image_proxy::proxy_url(original_image_src, config)
     } else {
         original_image_src.to_string()
     };
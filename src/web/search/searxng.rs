@@ -0,0 +1,341 @@
+//! The JSON that SearXNG returns for `/search?format=json`, for tools that
+//! were made for it. This is served at `/searxng/search` if
+//! `api.searxng_compatible` is set.
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use scraper::{Html, Selector};
+use serde::Serialize;
+use tracing::{error, Instrument};
+
+use crate::{
+    engines::{
+        self, Engine, EngineProgressUpdate, Infobox, ProgressUpdateData, ResponseForTab,
+        SearchQuery,
+    },
+    response::html_to_text,
+};
+
+pub async fn response(query: SearchQuery) -> Response {
+    let query_string = query.query.clone();
+    let config = query.config.clone();
+
+    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+    let search_future =
+        tokio::spawn(async move { engines::search(&query, progress_tx).await }.in_current_span());
+    // searxng's suggestions are other queries you might want to search for, which
+    // is what autocomplete gives us
+    let (search_result, suggestions) =
+        tokio::join!(search_future, engines::autocomplete(&config, &query_string));
+    if let Err(e) = search_result {
+        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+    }
+    let suggestions = suggestions.unwrap_or_else(|err| {
+        error!(
+            "Autocomplete error for {}: {}",
+            config.logging.loggable_query(&query_string),
+            config.logging.loggable_error(&err, &query_string)
+        );
+        Vec::new()
+    });
+
+    let mut response = None;
+    let mut post_search_infobox = None;
+    let mut unresponsive_engines = Vec::new();
+    while let Some(progress_update) = progress_rx.recv().await {
+        match progress_update.data {
+            ProgressUpdateData::Engine {
+                engine,
+                update: EngineProgressUpdate::Error(message),
+            } => unresponsive_engines.push((engine, message)),
+            ProgressUpdateData::Engine { .. } => {}
+            ProgressUpdateData::Response(r) => response = Some(r),
+            ProgressUpdateData::PostSearchInfobox(infobox) => {
+                post_search_infobox = Some(infobox);
+            }
+        }
+    }
+    Json(SearxngResponse::new(
+        query_string,
+        response,
+        post_search_infobox,
+        unresponsive_engines,
+        suggestions,
+    ))
+    .into_response()
+}
+
+#[derive(Serialize)]
+pub struct SearxngResponse {
+    query: String,
+    number_of_results: usize,
+    results: Vec<SearxngResult>,
+    answers: Vec<SearxngAnswer>,
+    corrections: Vec<String>,
+    infoboxes: Vec<SearxngInfobox>,
+    suggestions: Vec<String>,
+    /// Pairs of engine names and error messages.
+    unresponsive_engines: Vec<(&'static str, String)>,
+}
+
+#[derive(Serialize)]
+struct SearxngResult {
+    url: String,
+    title: String,
+    content: String,
+    engine: &'static str,
+    engines: Vec<&'static str>,
+    score: f64,
+    category: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    img_src: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail_src: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SearxngAnswer {
+    answer: String,
+    url: Option<String>,
+    engine: &'static str,
+}
+
+#[derive(Serialize)]
+struct SearxngInfobox {
+    infobox: String,
+    id: Option<String>,
+    content: String,
+    engine: &'static str,
+    engines: Vec<&'static str>,
+    urls: Vec<SearxngUrl>,
+}
+
+#[derive(Serialize)]
+struct SearxngUrl {
+    title: String,
+    url: String,
+}
+
+impl SearxngResponse {
+    pub fn new(
+        query: String,
+        response: Option<ResponseForTab>,
+        post_search_infobox: Option<Infobox>,
+        unresponsive_engines: Vec<(Engine, String)>,
+        suggestions: Vec<String>,
+    ) -> Self {
+        let mut results = Vec::new();
+        let mut answers = Vec::new();
+        let mut infoboxes = Vec::new();
+        match response {
+            Some(ResponseForTab::All(r)) => {
+                // searxng doesn't have featured snippets, so it's put first in the results
+                if let Some(snippet) = r.featured_snippet {
+                    results.push(SearxngResult {
+                        url: snippet.url,
+                        title: snippet.title,
+                        content: snippet.description,
+                        engine: snippet.engine.id(),
+                        engines: vec![snippet.engine.id()],
+                        score: 0.,
+                        category: "general",
+                        img_src: None,
+                        thumbnail_src: None,
+                    });
+                }
+                results.extend(r.search_results.into_iter().map(|r| {
+                    let engines = r.engines.iter().map(Engine::id).collect::<Vec<_>>();
+                    SearxngResult {
+                        url: r.result.url,
+                        title: r.result.title,
+                        content: r.result.description,
+                        engine: engines.first().copied().unwrap_or_default(),
+                        engines,
+                        score: r.score,
+                        category: "general",
+                        img_src: None,
+                        thumbnail_src: None,
+                    }
+                }));
+                answers.extend(r.answer.map(|answer| SearxngAnswer {
+                    answer: html_to_text(&answer.html.0),
+                    url: None,
+                    engine: answer.engine.id(),
+                }));
+                infoboxes.extend(r.infobox.map(SearxngInfobox::from));
+            }
+            Some(ResponseForTab::Images(r)) => {
+                results.extend(r.image_results.into_iter().map(|r| {
+                    let engines = r.engines.iter().map(Engine::id).collect::<Vec<_>>();
+                    SearxngResult {
+                        url: r.result.page_url,
+                        title: r.result.title,
+                        content: String::new(),
+                        engine: engines.first().copied().unwrap_or_default(),
+                        engines,
+                        score: r.score,
+                        category: "images",
+                        thumbnail_src: Some(r.result.image_url.clone()),
+                        img_src: Some(r.result.image_url),
+                    }
+                }));
+            }
+            None => {}
+        }
+        infoboxes.extend(post_search_infobox.map(SearxngInfobox::from));
+        let suggestions = suggestions
+            .into_iter()
+            .filter(|suggestion| !suggestion.eq_ignore_ascii_case(query.trim()))
+            .collect();
+
+        SearxngResponse {
+            query,
+            number_of_results: results.len(),
+            results,
+            answers,
+            corrections: vec![],
+            infoboxes,
+            suggestions,
+            unresponsive_engines: unresponsive_engines
+                .into_iter()
+                .map(|(engine, error)| (engine.id(), error))
+                .collect(),
+        }
+    }
+}
+
+impl From<Infobox> for SearxngInfobox {
+    fn from(infobox: Infobox) -> Self {
+        let fragment = Html::parse_fragment(&infobox.html.0);
+        let title = fragment
+            .select(&Selector::parse("h1, h2, h3").unwrap())
+            .next()
+            .map(|heading| heading.text().collect::<String>().trim().to_string())
+            .unwrap_or_else(|| infobox.engine.id().to_string());
+        let urls = fragment
+            .select(&Selector::parse("a[href]").unwrap())
+            .filter_map(|a| {
+                let href = a.value().attr("href")?;
+                href.starts_with("http").then(|| SearxngUrl {
+                    title: a.text().collect::<String>().trim().to_string(),
+                    url: href.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        SearxngInfobox {
+            infobox: title,
+            id: urls.first().map(|url| url.url.clone()),
+            content: html_to_text(&infobox.html.0),
+            engine: infobox.engine.id(),
+            engines: vec![infobox.engine.id()],
+            urls,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, sync::Arc};
+
+    use maud::PreEscaped;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        engines::{
+            EngineImageResult, EngineSearchResult, FeaturedSnippet, ImagesResponse, SearchResult,
+        },
+    };
+
+    #[test]
+    fn test_searxng_response() {
+        let response = engines::Response {
+            search_results: vec![SearchResult {
+                result: EngineSearchResult {
+                    url: "https://www.rust-lang.org/".to_string(),
+                    title: "Rust".to_string(),
+                    description: "A language".to_string(),
+                },
+                engines: BTreeSet::from([Engine::Google, Engine::Bing]),
+                score: 2.,
+            }],
+            featured_snippet: Some(FeaturedSnippet {
+                url: "https://en.wikipedia.org/wiki/Rust".to_string(),
+                title: "Rust (programming language)".to_string(),
+                description: "Rust is a language".to_string(),
+                engine: Engine::Google,
+            }),
+            answer: None,
+            infobox: Some(Infobox {
+                html: PreEscaped(
+                    r#"<h2>Rust</h2><p>A <b>language</b></p><a href="https://www.rust-lang.org/">Website</a><a href="/search?q=rust">Search</a>"#
+                        .to_string(),
+                ),
+                engine: Engine::Wikipedia,
+            }),
+            config: Arc::new(Config::default()),
+        };
+        let searxng = SearxngResponse::new(
+            "rust".to_string(),
+            Some(ResponseForTab::All(response)),
+            None,
+            vec![(Engine::Brave, "timed out".to_string())],
+            vec!["rust".to_string(), "rust game".to_string()],
+        );
+
+        // the featured snippet is the first result
+        assert_eq!(searxng.number_of_results, 2);
+        assert_eq!(searxng.results[0].title, "Rust (programming language)");
+        assert_eq!(searxng.results[1].engines, ["bing", "google"]);
+        assert_eq!(searxng.results[1].score, 2.);
+
+        let infobox = &searxng.infoboxes[0];
+        assert_eq!(infobox.infobox, "Rust");
+        assert_eq!(infobox.engine, "wikipedia");
+        assert_eq!(infobox.urls.len(), 1);
+        assert_eq!(infobox.urls[0].title, "Website");
+        assert_eq!(infobox.id.as_deref(), Some("https://www.rust-lang.org/"));
+
+        assert_eq!(searxng.suggestions, ["rust game"]);
+        assert_eq!(
+            searxng.unresponsive_engines,
+            [("brave", "timed out".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_searxng_image_response() {
+        let response = ImagesResponse {
+            image_results: vec![SearchResult {
+                result: EngineImageResult {
+                    image_url: "https://example.com/crab.png".to_string(),
+                    page_url: "https://example.com/crabs".to_string(),
+                    title: "A crab".to_string(),
+                    width: 100,
+                    height: 100,
+                },
+                engines: BTreeSet::from([Engine::Google]),
+                score: 1.,
+            }],
+            config: Arc::new(Config::default()),
+        };
+        let searxng = SearxngResponse::new(
+            "crab".to_string(),
+            Some(ResponseForTab::Images(response)),
+            None,
+            vec![],
+            vec![],
+        );
+        let result = &searxng.results[0];
+        assert_eq!(result.url, "https://example.com/crabs");
+        assert_eq!(result.category, "images");
+        assert_eq!(
+            result.img_src.as_deref(),
+            Some("https://example.com/crab.png")
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Sending every progress update of a search to API clients as it happens, so
+//! they can show results progressively like the HTML page does.
+
+use std::convert::Infallible;
+
+use async_stream::stream;
+use axum::{
+    body::Body,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use serde::Serialize;
+use tracing::{Instrument, Span};
+
+use crate::engines::{self, SearchQuery};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// Server-sent events, where the event name is the type of the update.
+    Sse,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl StreamFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            StreamFormat::Sse => "text/event-stream",
+            StreamFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    /// Encode a message, which must serialize to an object with a `type` field.
+    fn encode(self, message: &impl Serialize) -> Bytes {
+        let json = serde_json::to_value(message).unwrap_or_default();
+        match self {
+            StreamFormat::Sse => {
+                let event = json["type"].as_str().unwrap_or("message");
+                Bytes::from(format!("event: {event}\ndata: {json}\n\n"))
+            }
+            StreamFormat::Ndjson => Bytes::from(format!("{json}\n")),
+        }
+    }
+}
+
+/// Sent after every other update, so clients can tell the search is over (SSE
+/// clients would otherwise reconnect).
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum EndOfStream {
+    Done,
+    Error { message: String },
+}
+
+pub fn response(query: SearchQuery, format: StreamFormat) -> Response {
+    // the stream is polled after we return, so the search has to be put in the
+    // request's span manually
+    let span = Span::current();
+    let s = stream! {
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+        let search_future = tokio::spawn(
+            async move { engines::search(&query, progress_tx).await }.instrument(span),
+        );
+
+        while let Some(progress_update) = progress_rx.recv().await {
+            yield Ok::<_, Infallible>(format.encode(&progress_update));
+        }
+
+        let end = match search_future.await {
+            Ok(Ok(())) => EndOfStream::Done,
+            Ok(Err(e)) => EndOfStream::Error { message: e.to_string() },
+            Err(e) => EndOfStream::Error { message: e.to_string() },
+        };
+        yield Ok(format.encode(&end));
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type()),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        Body::from_stream(s),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::{Engine, EngineProgressUpdate, ProgressUpdate, ProgressUpdateData};
+
+    #[test]
+    fn test_encode_progress_update() {
+        let update = ProgressUpdate {
+            data: ProgressUpdateData::Engine {
+                engine: Engine::Google,
+                update: EngineProgressUpdate::Error("timed out".to_string()),
+            },
+            time_ms: 120,
+        };
+        let json = r#"{"type":"engine","engine":"google","update":{"state":"error","message":"timed out"},"time_ms":120}"#;
+        assert_eq!(
+            StreamFormat::Ndjson.encode(&update),
+            Bytes::from(format!("{json}\n"))
+        );
+        assert_eq!(
+            StreamFormat::Sse.encode(&update),
+            Bytes::from(format!("event: engine\ndata: {json}\n\n"))
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! A page showing how well each engine has been working recently, so it's easy
+//! to tell when one of them breaks.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use maud::{html, PreEscaped, DOCTYPE};
+
+use crate::{
+    config::Config,
+    engines::health::{self, EngineHealthSummary},
+    web::{access, head_html},
+};
+
+pub async fn get(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(config): Extension<Config>,
+    headers: HeaderMap,
+) -> Response {
+    if config.stats.admins.is_empty() {
+        return (StatusCode::NOT_FOUND, "Stats are disabled").into_response();
+    }
+    let Some(user) = access::authenticated_user(&headers, &config).await else {
+        return access::unauthorized_response(&config);
+    };
+    if !config.stats.admins.contains(&user) {
+        return (StatusCode::FORBIDDEN, "Only admins can see stats").into_response();
+    }
+
+    let summaries = health::summary(&config.circuit_breaker);
+
+    let wants_json = headers
+        .get(header::ACCEPT)
+        .is_some_and(|accept| accept == "application/json")
+        || params.get("format").is_some_and(|format| format == "json");
+    if wants_json {
+        return Json(summaries).into_response();
+    }
+
+    let html = html! {
+        (PreEscaped("<!-- source code: https://github.com/mat-1/metasearch2 -->\n"))
+        (DOCTYPE)
+        html lang="en" {
+            {(head_html(Some("stats"), &config))}
+            body {
+                div.main-container.stats-page {
+                    main {
+                        a.back-to-index-button href="/" { "Back" }
+                        h1 { "Engine stats" }
+                        @if summaries.is_empty() {
+                            p { "No searches have been made yet." }
+                        }
+                        @for summary in &summaries {
+                            (render_engine_summary(summary))
+                        }
+                    }
+                }
+            }
+        }
+    }
+    .into_string();
+
+    ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response()
+}
+
+fn render_engine_summary(summary: &EngineHealthSummary) -> PreEscaped<String> {
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    html! {
+        div.engine-stats {
+            h2 {
+                (summary.engine)
+                @if summary.circuit_open {
+                    " "
+                    span.progress-update-error { "(circuit breaker open)" }
+                }
+            }
+            @if let Some(degraded) = &summary.degraded {
+                p.engine-stats-degraded {
+                    "Degraded: "
+                    span.progress-update-error { (degraded) }
+                }
+            }
+            table {
+                tr {
+                    th { "Window" }
+                    th { "Requests" }
+                    th { "Success rate" }
+                    th { "Median latency" }
+                    th { "p95 latency" }
+                    th { "Average results" }
+                }
+                @for window in &summary.windows {
+                    tr {
+                        td { (window.window) }
+                        td { (window.requests) }
+                        td { (or_dash(window.success_rate.map(|r| format!("{:.1}%", r * 100.)))) }
+                        td { (or_dash(window.median_latency_ms.map(|ms| format!("{ms}ms")))) }
+                        td { (or_dash(window.p95_latency_ms.map(|ms| format!("{ms}ms")))) }
+                        td { (or_dash(window.average_results.map(|n| format!("{n:.1}")))) }
+                    }
+                }
+            }
+            @if let (Some(error), Some(time)) = (&summary.last_error, &summary.last_error_time) {
+                p.engine-stats-last-error {
+                    "Last error at " (time) ": "
+                    span.progress-update-error { (error) }
+                }
+            }
+        }
+    }
+}
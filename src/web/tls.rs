@@ -0,0 +1,242 @@
+//! Serving HTTPS ourselves, with certificates that are reloaded when the files
+//! change.
+
+use std::{
+    fs, io,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use eyre::{bail, eyre};
+use parking_lot::RwLock;
+use rustls::{
+    crypto::CryptoProvider,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    ServerConfig,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+use tracing::{debug, error, info};
+
+use crate::config::TlsConfig;
+
+/// Clients that take longer than this to finish the handshake are dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Create the acceptor that's shared by every HTTPS listener, and start
+/// watching the certificate for changes.
+pub fn acceptor(config: &TlsConfig) -> eyre::Result<TlsAcceptor> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let resolver = Arc::new(CertResolver(RwLock::new(Arc::new(load_certified_key(
+        config, &provider,
+    )?))));
+
+    let mut server_config = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver.clone());
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    if config.reload_interval_secs > 0 {
+        spawn_reloader(config.clone(), provider, resolver);
+    }
+
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+#[derive(Debug)]
+struct CertResolver(RwLock<Arc<CertifiedKey>>);
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.read().clone())
+    }
+}
+
+fn load_certified_key(config: &TlsConfig, provider: &CryptoProvider) -> eyre::Result<CertifiedKey> {
+    let certs = CertificateDer::pem_file_iter(&config.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| {
+            eyre!(
+                "couldn't read certificate from {}: {err}",
+                config.cert.display()
+            )
+        })?;
+    if certs.is_empty() {
+        bail!("no certificates found in {}", config.cert.display());
+    }
+    let key = PrivateKeyDer::from_pem_file(&config.key).map_err(|err| {
+        eyre!(
+            "couldn't read private key from {}: {err}",
+            config.key.display()
+        )
+    })?;
+    let signing_key = provider.key_provider.load_private_key(key)?;
+    Ok(CertifiedKey::new(certs, signing_key))
+}
+
+/// Check the modification times of the certificate and key every
+/// `reload_interval_secs`, and load them again if either changed. If the new
+/// files are broken then we keep using the old certificate.
+fn spawn_reloader(config: TlsConfig, provider: Arc<CryptoProvider>, resolver: Arc<CertResolver>) {
+    let modified_times = move |config: &TlsConfig| {
+        let modified = |path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        (modified(&config.cert), modified(&config.key))
+    };
+
+    tokio::spawn(async move {
+        let mut last_modified: (Option<SystemTime>, Option<SystemTime>) = modified_times(&config);
+        let mut interval = tokio::time::interval(Duration::from_secs(config.reload_interval_secs));
+        // the first tick is immediate
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let modified = modified_times(&config);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            match load_certified_key(&config, &provider) {
+                Ok(key) => {
+                    *resolver.0.write() = Arc::new(key);
+                    info!("Reloaded TLS certificate from {}", config.cert.display());
+                }
+                Err(err) => {
+                    error!("Couldn't reload TLS certificate, still using the old one: {err}");
+                }
+            }
+        }
+    });
+}
+
+/// A listener that only gives axum connections that finished the TLS
+/// handshake. Handshakes happen in their own tasks so slow clients can't hold
+/// up everyone else.
+pub struct TlsListener {
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    pub async fn bind(addr: SocketAddr, acceptor: TlsAcceptor) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let (tx, incoming) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, peer) = tokio::select! {
+                    // the server shut down
+                    _ = tx.closed() => break,
+                    res = listener.accept() => match res {
+                        Ok(conn) => conn,
+                        Err(err) => {
+                            // probably out of file descriptors, so wait a bit
+                            error!("Couldn't accept connection: {err}");
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                            continue;
+                        }
+                    },
+                };
+
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send((stream, peer)).await;
+                        }
+                        Ok(Err(err)) => debug!("TLS handshake with {peer} failed: {err}"),
+                        Err(_) => debug!("TLS handshake with {peer} timed out"),
+                    }
+                });
+            }
+        });
+
+        Ok(TlsListener {
+            incoming,
+            local_addr,
+        })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(conn) => conn,
+            // the accept loop only stops when we're dropped, so this can't happen
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// A router that redirects every request to the same URL on HTTPS. `https_port`
+/// is added to the URL if it isn't 443.
+pub fn redirect_router(https_port: u16) -> Router {
+    Router::new()
+        .fallback(redirect_to_https)
+        .with_state(https_port)
+}
+
+async fn redirect_to_https(
+    State(https_port): State<u16>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    let Some(host) = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+    else {
+        return (StatusCode::BAD_REQUEST, "Missing Host header").into_response();
+    };
+    let host = strip_port(host);
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+    let location = if https_port == 443 {
+        format!("https://{host}{path}")
+    } else {
+        format!("https://{host}:{https_port}{path}")
+    };
+    Redirect::permanent(&location).into_response()
+}
+
+fn strip_port(host: &str) -> &str {
+    match host.rfind(':') {
+        // the colon could be part of an ipv6 address like [::1]
+        Some(i) if !host[i..].contains(']') => &host[..i],
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_port() {
+        assert_eq!(strip_port("example.com:80"), "example.com");
+        assert_eq!(strip_port("example.com"), "example.com");
+        assert_eq!(strip_port("[::1]:8080"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+    }
+}